@@ -1,11 +1,17 @@
 use std::fmt::{Display, Formatter};
 use std::path::PathBuf;
+use std::str::FromStr;
 
 use clap::Parser;
 use log::{info, warn};
 use once_cell::sync::OnceCell;
 
-use crate::color::FALLBACK_COLOR;
+use crate::color::{Color, FALLBACK_COLOR, PaletteMode};
+use crate::color_mapping::{BlendMode, ColorizeScope, ColorMapping};
+use crate::export::ExportFormat;
+use crate::rainbow::RainbowMode;
+use crate::renderer::DelayPolicy;
+use crate::selection::SelectionStrategy;
 
 ///Globally accessible argument results. Never changes after initialized
 pub static ARGUMENTS: OnceCell<Arguments> = OnceCell::new();
@@ -51,13 +57,57 @@ pub struct Arguments {
     //#[clap(short='o', long, required=false, long_example="FF0080")]
     //#[clap(short='o', long, required=false, example="FF0080")]
     #[clap(short = 'o', long, required = false)]
-    ///Color in hex format that should be used for not colored animations, e.g. -o FF0080 for magenta
+    ///Color that should be used for not colored animations, e.g. -o FF0080, -o "#F08", -o "rgb(255, 0, 128)" or -o hotpink
     pub default_color: Option<String>,
 
     #[clap(short = 'p', long, required = false)]
     ///The path to a color palette
     pub palette: Option<PathBuf>,
 
+    #[clap(long, value_enum, required = false)]
+    ///How colors are chosen from the palette. Overwrites the mode of the palette file
+    pub palette_mode: Option<PaletteMode>,
+
+    #[clap(long, required = false, default_value = "0", allow_hyphen_values = true)]
+    ///Hours the local time is ahead of UTC, for the time-of-day palette mode
+    pub utc_offset: f32,
+
+    #[clap(long)]
+    ///Keep the random color of the base for all blinks of a cycle
+    pub lock_cycle_color: bool,
+
+    #[clap(long, value_enum, required = false, default_value = "flat")]
+    ///How the gray levels of grayscale animations are mapped onto the overwrite color
+    pub color_mapping: ColorMapping,
+
+    #[clap(long, value_enum, required = false, default_value = "animation")]
+    ///If whole animations, single frames or single pixels have to be grayscale to get the overwrite color
+    pub colorize_scope: ColorizeScope,
+
+    #[clap(long = "gradient-stop", required = false)]
+    ///Hex color of the gradient for the gradient color mapping, from dark to bright. Can be used multiple times
+    pub gradient_stops: Vec<String>,
+
+    #[clap(long, value_enum, required = false)]
+    ///Blend the overwrite color with all animations, colored ones included, instead of the color mapping
+    pub blend_mode: Option<BlendMode>,
+
+    #[clap(long, required = false, default_value = "50")]
+    ///Percentage of the overwrite color the tint blend mode mixes in
+    pub tint: u8,
+
+    #[clap(long, value_enum, required = false)]
+    ///Color grayscale animations, base and blinks by a hue, that moves over time
+    pub rainbow: Option<RainbowMode>,
+
+    #[clap(long, required = false, default_value = "60")]
+    ///How many degrees the hue of the rainbow moves per second
+    pub rainbow_speed: f32,
+
+    #[clap(long, required = false, default_value = "1")]
+    ///Saturation of the rainbow between 0 and 1
+    pub rainbow_saturation: f32,
+
     #[clap(short = 'P', long, required = false)]
     ///The path to a playlist
     pub playlist: Option<PathBuf>,
@@ -70,8 +120,44 @@ pub struct Arguments {
     ///Set the TCP port that is to use for receiving animations
     pub inject_port: u16,
 
+    #[clap(short = 't', long, required = false)]
+    ///Only play animations with this tag, which are the names of their subfolders or the tags of their metadata
+    pub tag: Option<String>,
+
+    #[clap(short = 'I', long = "ignore", required = false)]
+    ///Ignore files and folders matching this pattern when looking for animations, e.g. -I "draft_*". Can be used multiple times
+    pub ignore_patterns: Vec<String>,
+
+    #[clap(long, value_enum, required = false, default_value = "no-repeat")]
+    ///How blinks are chosen
+    pub blink_selection: SelectionStrategy,
+
+    #[clap(long, value_enum, required = false, default_value = "shuffle-bag")]
+    ///How other animations are chosen
+    pub other_selection: SelectionStrategy,
+
+    #[clap(long, required = false, default_value = "1")]
+    ///How many of the last played animations the no-repeat selection excludes
+    pub selection_window: usize,
+
+    #[clap(long, value_enum, required = false, default_value = "browser")]
+    ///How frame delays of 0 or 10 ms are handled
+    pub frame_delay_policy: DelayPolicy,
+
+    #[clap(long, required = false, default_value = "20")]
+    ///Minimum milliseconds a frame is shown, when the frame delay policy is clamp
+    pub min_frame_delay: u32, //ms
+
+    #[clap(long)]
+    ///Skip frames, when the renderer falls behind the timing of an animation
+    pub skip_late_frames: bool,
+
+    #[clap(long, env = "TASBOT_EYES_SEED", required = false)]
+    ///Seed for all random decisions. The same seed and animations replay the exact same sequence
+    pub seed: Option<u64>,
+
     #[command(subcommand)]
-    ///Which renderer to use
+    ///Which renderer (or tool) to use
     pub renderer: RendererType,
 }
 
@@ -145,6 +231,45 @@ pub enum RendererType {
 
     ///Render no animation at all (for debugging or testing)
     Silent,
+
+    ///Check animations for problems, like tiny frame delays, and exit
+    Check {
+        ///Paths to animations or directories, that are searched recursively
+        #[clap(required = true)]
+        paths: Vec<PathBuf>,
+    },
+
+    ///Extract a palette of the dominant colors of animations and exit
+    ExtractPalette {
+        ///Paths to animations or directories, that are searched recursively
+        #[clap(required = true)]
+        paths: Vec<PathBuf>,
+
+        #[clap(short = 'o', long, required = true)]
+        ///Path of the JSON palette, that is to write
+        output: PathBuf,
+
+        #[clap(short = 'n', long, default_value = "8")]
+        ///How many colors the palette has at most
+        count: usize,
+    },
+
+    ///Export an animation to another format and exit
+    Export {
+        ///Path to the animation that is to export
+        input: PathBuf,
+
+        ///Path of the exported file, or the directory for PNG frames
+        output: PathBuf,
+
+        #[clap(short = 'f', long, value_enum, default_value = "gif")]
+        ///Format to export to
+        format: ExportFormat,
+
+        #[clap(short = 'n', long, required = false)]
+        ///Name of the arrays in C headers. Default is the file name of the output
+        name: Option<String>,
+    },
 }
 
 impl Display for Arguments {
@@ -160,9 +285,29 @@ impl Display for Arguments {
         result.push_str(&*format!("\t-Overwrite colors of grayscale animations, base and blinks: {}\n", self.color_overwrite_all.to_string()));
         result.push_str(&*format!("\t-Color for base, blinks and grayscale animations: #{}\n", self.default_color.clone().unwrap_or(FALLBACK_COLOR.to_string())));
         result.push_str(&*format!("\t-Color palette for random colors: {}\n", self.palette.clone().unwrap_or(PathBuf::from("None")).display()));
+        result.push_str(&*format!("\t-Palette mode: {}\n", self.palette_mode.map(|mode| mode.to_string()).unwrap_or("Palette file".to_owned())));
+        result.push_str(&*format!("\t-UTC offset: {} h\n", self.utc_offset));
+        result.push_str(&*format!("\t-Lock color for base and blinks of a cycle: {}\n", self.lock_cycle_color));
+        result.push_str(&*format!("\t-Color mapping: {}\n", self.color_mapping));
+        result.push_str(&*format!("\t-Colorize scope: {}\n", self.colorize_scope));
+        result.push_str(&*format!("\t-Gradient stops: {:?}\n", self.gradient_stops));
+        result.push_str(&*format!("\t-Blend mode: {}\n", self.blend_mode.map(|mode| mode.to_string()).unwrap_or("None".to_owned())));
+        result.push_str(&*format!("\t-Tint: {} %\n", self.tint));
+        result.push_str(&*format!("\t-Rainbow mode: {}\n", self.rainbow.map(|mode| mode.to_string()).unwrap_or("None".to_owned())));
+        result.push_str(&*format!("\t-Rainbow speed: {} °/s\n", self.rainbow_speed));
+        result.push_str(&*format!("\t-Rainbow saturation: {}\n", self.rainbow_saturation));
         result.push_str(&*format!("\t-Playlist to play: {}\n", self.playlist.clone().unwrap_or(PathBuf::from("None")).display()));
         result.push_str(&*format!("\t-Continue with normal flow after playlist: {}\n", self.continue_after_playlist.to_string()));
-        result.push_str(&*format!("\t-TCP port for receiving animations: {}", self.inject_port));
+        result.push_str(&*format!("\t-TCP port for receiving animations: {}\n", self.inject_port));
+        result.push_str(&*format!("\t-Only play animations tagged with: {}\n", self.tag.clone().unwrap_or("None".to_owned())));
+        result.push_str(&*format!("\t-Ignore patterns: {:?}\n", self.ignore_patterns));
+        result.push_str(&*format!("\t-Selection of blinks: {}\n", self.blink_selection));
+        result.push_str(&*format!("\t-Selection of other animations: {}\n", self.other_selection));
+        result.push_str(&*format!("\t-Window of the no-repeat selection: {}\n", self.selection_window));
+        result.push_str(&*format!("\t-Frame delay policy: {:?}\n", self.frame_delay_policy));
+        result.push_str(&*format!("\t-Minimum frame delay: {} ms\n", self.min_frame_delay));
+        result.push_str(&*format!("\t-Skip late frames: {}\n", self.skip_late_frames));
+        result.push_str(&*format!("\t-Random seed: {}", self.seed.map(|seed| seed.to_string()).unwrap_or("Random".to_owned())));
 
         write!(f, "{}", result)
     }
@@ -183,6 +328,11 @@ fn check_arguments(mut raw_args: Arguments) -> Arguments {
         raw_args.playback_speed = 1.0;
     }
 
+    if raw_args.tint > 100 {
+        warn!("Tint can't be more then 100 %! Using 100 %");
+        raw_args.tint = 100;
+    }
+
     if raw_args.max_delay < raw_args.min_delay {
         warn!("Maximum delay between blinks can't be smaller then minimum delay. Swapping them.");
         let temp = raw_args.min_delay;
@@ -191,7 +341,7 @@ fn check_arguments(mut raw_args: Arguments) -> Arguments {
     }
 
     //Attempt to convert it. If possible, everything is good
-    raw_args.default_color = match u32::from_str_radix(&raw_args.default_color.clone().unwrap_or(FALLBACK_COLOR.to_string()), 16) {
+    raw_args.default_color = match Color::from_str(&raw_args.default_color.clone().unwrap_or(FALLBACK_COLOR.to_string())) {
         Ok(_) => { raw_args.default_color } //nothing changes
         Err(e) => {
             warn!("Given color is not in a valid format. Using default color: {}", e.to_string());
@@ -215,9 +365,29 @@ pub fn fallback_arguments() -> Arguments {
         color_overwrite_all: false,
         default_color: None,
         palette: None,
+        palette_mode: None,
+        utc_offset: 0.0,
+        lock_cycle_color: false,
+        color_mapping: ColorMapping::Flat,
+        colorize_scope: ColorizeScope::Animation,
+        gradient_stops: Vec::new(),
+        blend_mode: None,
+        tint: 50,
+        rainbow: None,
+        rainbow_speed: 60.0,
+        rainbow_saturation: 1.0,
         playlist: None,
         continue_after_playlist: false,
         inject_port: 8082,
+        tag: None,
+        ignore_patterns: Vec::new(),
+        blink_selection: SelectionStrategy::NoRepeat,
+        other_selection: SelectionStrategy::ShuffleBag,
+        selection_window: 1,
+        frame_delay_policy: DelayPolicy::Browser,
+        min_frame_delay: 20,
+        skip_late_frames: false,
+        seed: None,
         renderer: RendererType::Silent,
     }
 }
@@ -225,6 +395,9 @@ pub fn fallback_arguments() -> Arguments {
 #[cfg(test)]
 mod tests{
     use crate::arguments::{Arguments, check_arguments, RendererType};
+    use crate::color_mapping::{ColorizeScope, ColorMapping};
+    use crate::renderer::DelayPolicy;
+    use crate::selection::SelectionStrategy;
 
     #[test]
     fn test_check_arguments() {
@@ -238,9 +411,29 @@ mod tests{
             color_overwrite_all: false,
             default_color: Some("ffffff".to_owned()),
             palette: None,
+            palette_mode: None,
+            utc_offset: 0.0,
+            lock_cycle_color: false,
+            color_mapping: ColorMapping::Flat,
+            colorize_scope: ColorizeScope::Animation,
+            gradient_stops: Vec::new(),
+            blend_mode: None,
+            tint: 50,
+            rainbow: None,
+            rainbow_speed: 60.0,
+            rainbow_saturation: 1.0,
             playlist: None,
             continue_after_playlist: false,
             inject_port: 0,
+            tag: None,
+            ignore_patterns: Vec::new(),
+            blink_selection: SelectionStrategy::NoRepeat,
+            other_selection: SelectionStrategy::ShuffleBag,
+            selection_window: 1,
+            frame_delay_policy: DelayPolicy::Browser,
+            min_frame_delay: 20,
+            skip_late_frames: false,
+            seed: None,
             renderer: RendererType::Silent,
         };
 
@@ -254,9 +447,29 @@ mod tests{
             color_overwrite_all: false,
             default_color: None,
             palette: None,
+            palette_mode: None,
+            utc_offset: 0.0,
+            lock_cycle_color: false,
+            color_mapping: ColorMapping::Flat,
+            colorize_scope: ColorizeScope::Animation,
+            gradient_stops: Vec::new(),
+            blend_mode: None,
+            tint: 50,
+            rainbow: None,
+            rainbow_speed: 60.0,
+            rainbow_saturation: 1.0,
             playlist: None,
             continue_after_playlist: false,
             inject_port: 0,
+            tag: None,
+            ignore_patterns: Vec::new(),
+            blink_selection: SelectionStrategy::NoRepeat,
+            other_selection: SelectionStrategy::ShuffleBag,
+            selection_window: 1,
+            frame_delay_policy: DelayPolicy::Browser,
+            min_frame_delay: 20,
+            skip_late_frames: false,
+            seed: None,
             renderer: RendererType::Silent,
         };
 
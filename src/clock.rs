@@ -0,0 +1,103 @@
+#[cfg(test)]
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A source of time, that the renderers and the program flow wait with. This way timing code can be
+/// tested with a `VirtualClock`, without actually sleeping
+pub trait Clock: Send + Sync {
+    /// The time that passed since the clock was created
+    fn now(&self) -> Duration;
+
+    /// Wait for the given duration
+    fn sleep(&self, duration: Duration);
+}
+
+/// The real clock, that actually sleeps
+pub struct SystemClock {
+    /// When the clock was created
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        SystemClock { start: Instant::now() }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// A clock for tests, that doesn't sleep at all. Sleeping just moves its time forward
+#[cfg(test)]
+pub struct VirtualClock {
+    /// The current time of the clock
+    now: Mutex<Duration>,
+}
+
+#[cfg(test)]
+impl VirtualClock {
+    pub fn new() -> Self {
+        VirtualClock { now: Mutex::new(Duration::ZERO) }
+    }
+
+    /// Move the time of the clock forward, without anything waiting for it
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap_or_else(|e| e.into_inner());
+        *now += duration;
+    }
+}
+
+#[cfg(test)]
+impl Default for VirtualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+impl Clock for VirtualClock {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_virtual_clock() {
+        let clock = VirtualClock::new();
+        assert_eq!(clock.now(), Duration::ZERO);
+
+        clock.sleep(Duration::from_millis(420));
+        clock.advance(Duration::from_millis(80));
+        assert_eq!(clock.now(), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_system_clock() {
+        let clock = SystemClock::new();
+        let before = clock.now();
+        clock.sleep(Duration::from_millis(1));
+        assert!(clock.now() > before);
+    }
+}
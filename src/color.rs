@@ -1,17 +1,32 @@
 use std::fmt::{Display, Formatter};
 use std::num::ParseIntError;
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use colored::Colorize;
 use log::{info, warn};
-use once_cell::sync::OnceCell;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
 use rand::seq::SliceRandom;
-use rand::thread_rng;
 use rs_ws281x::RawColor;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::arguments::{ARGUMENTS, fallback_arguments};
-use crate::file_operations::read_palette;
+use crate::color::names::css_color;
+use crate::color::palette::read_palette_colors;
+use crate::random::with_rng;
+use crate::settings::{default_color, palette_path, set_palette, with_palette};
+
+///Extract palettes from animations
+pub mod extract;
+
+///Named CSS colors
+pub mod names;
+
+///Readers for the palette files of other programs
+pub mod palette;
 
 pub const BLACK: Color = Color { r: 0, g: 0, b: 0 };
 pub const WHITE: Color = Color { r: 255, g: 255, b: 255 };
@@ -28,8 +43,8 @@ pub const FALLBACK_COLOR: Color = WHITE;
 ///The default palette, that random colors can get chosen from
 const DEFAULT_PALETTE: [Color; 6] = [RED, YELLOW, GREEN, CYAN, BLUE, PURPLE];
 
-///Once the color palette is initialized, make it available globally
-pub static COLOR_PALETTE: OnceCell<Vec<Color>> = OnceCell::new();
+///The length of a day, which the time-of-day palette mode spreads its colors over
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
 
 #[derive(Error, Debug)]
 pub enum ColorError {
@@ -39,6 +54,9 @@ pub enum ColorError {
     #[error("An JSON error occurred: {0}")]
     JSON(#[from] serde_json::Error),
 
+    #[error("Can't read palette: {0}")]
+    Palette(String),
+
     #[error("An error occurred: {0}")]
     Other(String),
 }
@@ -75,20 +93,25 @@ impl Color {
         }
     }
 
-    /// Convert a given hex color string to an color
+    /// Convert a given hex color string to an color. The string may start with `#` and may be the 3 digit shorthand
     ///
     /// # Input
-    /// `hex_string`: The string that contains a hex color
+    /// `hex_string`: The string that contains a hex color, e.g. `FF0080`, `#FF0080` or `#F08`
     ///
     /// # Output
     /// A `Result<Color, String>`, indicating if the conversion was successfully. If so, a `Color` structure is
     /// wrapped in it. If not, a `String` is returned as error (to be printed).
     pub fn from_hex_string(hex_string: &str) -> Result<Color, String> {
-        if hex_string.len() != 6 {
-            return Err(format!("Hex string is to short! Must be 6 but is {}", hex_string.len()));
-        }
+        let hex_string = hex_string.strip_prefix('#').unwrap_or(hex_string);
+
+        //Expand the shorthand, so every digit is doubled
+        let hex_string = match hex_string.len() {
+            3 => hex_string.chars().flat_map(|c| [c, c]).collect(),
+            6 => hex_string.to_owned(),
+            len => return Err(format!("Hex string has the wrong length! Must be 3 or 6 but is {}", len)),
+        };
 
-        match u32::from_str_radix(hex_string, 16) {
+        match u32::from_str_radix(&hex_string, 16) {
             Ok(c) => {
                 Ok(Color::from_hex(c))
             }
@@ -99,6 +122,29 @@ impl Color {
         }
     }
 
+    /// Convert a CSS `rgb()` function to a color
+    ///
+    /// # Input
+    /// `rgb`: The function, e.g. `rgb(255, 0, 128)` or `rgb(255 0 128)`
+    ///
+    /// # Output
+    /// A `Result<Color, String>` with the color or the reason it can't be read
+    fn from_rgb_function(rgb: &str) -> Result<Color, String> {
+        let inner = rgb.strip_prefix("rgb(")
+            .and_then(|rest| rest.strip_suffix(')'))
+            .ok_or_else(|| format!("Invalid rgb() function: {}", rgb))?;
+
+        let channels: Vec<u8> = inner.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|channel| !channel.is_empty())
+            .map(|channel| channel.parse::<u8>().map_err(|e| format!("Invalid channel ({}) in {}: {}", channel, rgb, e)))
+            .collect::<Result<_, _>>()?;
+
+        match channels[..] {
+            [r, g, b] => Ok(Color { r, g, b }),
+            _ => Err(format!("rgb() needs 3 channels but has {}: {}", channels.len(), rgb)),
+        }
+    }
+
     /// Convert the color to a `RawColor` that is used to set the LED color
     ///
     /// # Output
@@ -111,6 +157,190 @@ impl Color {
             0,
         ]
     }
+
+    /// Darken this color by another one. White keeps this color, black results in black
+    pub fn multiply(self, other: Color) -> Color {
+        self.blend_channels(other, |a, b| a * b)
+    }
+
+    /// Brighten this color by another one. Black keeps this color, white results in white
+    pub fn screen(self, other: Color) -> Color {
+        self.blend_channels(other, |a, b| 1.0 - (1.0 - a) * (1.0 - b))
+    }
+
+    /// Multiply the dark and screen the bright channels of this color, which raises the contrast
+    pub fn overlay(self, other: Color) -> Color {
+        self.blend_channels(other, |a, b| if a < 0.5 { 2.0 * a * b } else { 1.0 - 2.0 * (1.0 - a) * (1.0 - b) })
+    }
+
+    /// Replace the hue of this color by the one of another color, while keeping saturation and value
+    pub fn replace_hue(self, other: Color) -> Color {
+        let (_, saturation, value) = self.to_hsv();
+        let (hue, _, _) = other.to_hsv();
+        Color::from_hsv(hue, saturation, value)
+    }
+
+    /// Mix another color into this one
+    ///
+    /// # Input
+    /// * `other`: The color that is mixed in
+    /// * `amount`: How much of the other color is used, between 0.0 and 1.0
+    pub fn tint(self, other: Color, amount: f32) -> Color {
+        let amount = amount.clamp(0.0, 1.0);
+        self.blend_channels(other, |a, b| a + (b - a) * amount)
+    }
+
+    /// Combine every channel of this color with the one of another color
+    ///
+    /// # Input
+    /// * `other`: The other color
+    /// * `f`: Combines the channels, which are between 0.0 and 1.0
+    fn blend_channels<F: Fn(f32, f32) -> f32>(self, other: Color, f: F) -> Color {
+        let channel = |a: u8, b: u8| (f(a as f32 / 255.0, b as f32 / 255.0).clamp(0.0, 1.0) * 255.0).round() as u8;
+
+        Color {
+            r: channel(self.r, other.r),
+            g: channel(self.g, other.g),
+            b: channel(self.b, other.b),
+        }
+    }
+
+    /// Convert the color to HSV
+    ///
+    /// # Output
+    /// The hue in degrees between 0.0 and 360.0, saturation and value between 0.0 and 1.0
+    pub fn to_hsv(self) -> (f32, f32, f32) {
+        let (r, g, b) = (self.r as f32 / 255.0, self.g as f32 / 255.0, self.b as f32 / 255.0);
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+
+        let hue = if delta == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+        let saturation = if max == 0.0 { 0.0 } else { delta / max };
+
+        (hue, saturation, max)
+    }
+
+    /// Get a color from HSV
+    ///
+    /// # Input
+    /// * `hue`: The hue in degrees, values outside of 0.0 to 360.0 wrap around
+    /// * `saturation`: The saturation between 0.0 and 1.0
+    /// * `value`: The value between 0.0 and 1.0
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Color {
+        let hue = hue.rem_euclid(360.0);
+        let chroma = value * saturation;
+        let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+        let m = value - chroma;
+
+        let (r, g, b) = match hue as u16 / 60 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let channel = |c: f32| ((c + m).clamp(0.0, 1.0) * 255.0).round() as u8;
+
+        Color { r: channel(r), g: channel(g), b: channel(b) }
+    }
+
+    /// Convert the color to [OKLab](https://bottosson.github.io/posts/oklab/), where distances match
+    /// how different colors are perceived
+    ///
+    /// # Output
+    /// The lightness between 0.0 and 1.0, followed by the green-red and the blue-yellow axis
+    pub fn to_oklab(self) -> (f32, f32, f32) {
+        let (r, g, b) = (to_linear(self.r), to_linear(self.g), to_linear(self.b));
+
+        let l = (0.412_221_46 * r + 0.536_332_55 * g + 0.051_445_995 * b).cbrt();
+        let m = (0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b).cbrt();
+        let s = (0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b).cbrt();
+
+        (
+            0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+            1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+            0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+        )
+    }
+
+    /// Get a color from OKLab. Colors outside of sRGB are clamped
+    ///
+    /// # Input
+    /// * `lightness`: The lightness between 0.0 and 1.0
+    /// * `a`: The green-red axis
+    /// * `b`: The blue-yellow axis
+    pub fn from_oklab(lightness: f32, a: f32, b: f32) -> Color {
+        let l = (lightness + 0.396_337_78 * a + 0.215_803_76 * b).powi(3);
+        let m = (lightness - 0.105_561_346 * a - 0.063_854_17 * b).powi(3);
+        let s = (lightness - 0.089_484_18 * a - 1.291_485_5 * b).powi(3);
+
+        Color {
+            r: from_linear(4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s),
+            g: from_linear(-1.268_438 * l + 2.609_757_4 * m - 0.341_319_38 * s),
+            b: from_linear(-0.004_196_086_3 * l - 0.703_418_6 * m + 1.707_614_7 * s),
+        }
+    }
+
+    /// Interpolate perceptually to another color, using OKLab
+    ///
+    /// # Input
+    /// * `other`: The color at 1.0
+    /// * `t`: The position between both colors, between 0.0 and 1.0
+    pub fn mix(self, other: Color, t: f32) -> Color {
+        let t = t.clamp(0.0, 1.0);
+        let (l1, a1, b1) = self.to_oklab();
+        let (l2, a2, b2) = other.to_oklab();
+
+        Color::from_oklab(l1 + (l2 - l1) * t, a1 + (a2 - a1) * t, b1 + (b2 - b1) * t)
+    }
+
+    /// Rotate the hue of the color, while keeping saturation and value
+    ///
+    /// # Input
+    /// `degrees`: How far the hue is rotated
+    pub fn rotate_hue(self, degrees: f32) -> Color {
+        let (hue, saturation, value) = self.to_hsv();
+        Color::from_hsv(hue + degrees, saturation, value)
+    }
+}
+
+/// Convert an sRGB channel to linear light
+fn to_linear(channel: u8) -> f32 {
+    let c = channel as f32 / 255.0;
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+/// Convert linear light to an sRGB channel
+fn from_linear(c: f32) -> u8 {
+    let c = if c <= 0.003_130_8 { 12.92 * c } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+    (c.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    /// Read a color from a hex string (with or without `#`, 6 or 3 digits), a CSS `rgb()` function or a CSS color name
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.to_ascii_lowercase().starts_with("rgb(") {
+            return Color::from_rgb_function(&s.to_ascii_lowercase());
+        }
+
+        match Color::from_hex_string(s) {
+            Ok(color) => Ok(color),
+            Err(e) => css_color(s).ok_or(e),
+        }
+    }
 }
 
 impl Display for Color {
@@ -120,29 +350,239 @@ impl Display for Color {
     }
 }
 
-/// Initialize the color palette `OnceCell<>` with either
+#[derive(clap::ValueEnum, Serialize, Deserialize, Copy, Clone, Default, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+/// How the colors are chosen from a palette
+pub enum PaletteMode {
+    /// Choose a random color by the weights
+    #[default]
+    Random,
+
+    /// Choose a random color by the weights, but never the last one again
+    NoRepeat,
+
+    /// Cycle through the colors in the order of the palette. The weights are ignored
+    Sequential,
+
+    /// Spread the colors over the day, each getting a share by its weight
+    TimeOfDay,
+}
+
+impl Display for PaletteMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            PaletteMode::Random => "random",
+            PaletteMode::NoRepeat => "no-repeat",
+            PaletteMode::Sequential => "sequential",
+            PaletteMode::TimeOfDay => "time-of-day",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+/// A palette of weighted colors, that remembers the last chosen one
+pub struct ColorPalette {
+    /// The colors of the palette
+    pub colors: Vec<Color>,
+
+    /// The weight of every color. Negative weights are treated as 0.0
+    pub weights: Vec<f32>,
+
+    /// How the colors are chosen
+    pub mode: PaletteMode,
+
+    /// The index of the last chosen color
+    last: Option<usize>,
+}
+
+impl ColorPalette {
+    /// Create a new palette
+    ///
+    /// # Input
+    /// * `colors`: The colors of the palette
+    /// * `weights`: The weight of every color
+    /// * `mode`: How the colors are chosen
+    pub fn new(colors: Vec<Color>, weights: Vec<f32>, mode: PaletteMode) -> Self {
+        let weights = weights.into_iter().map(|weight| if weight > 0.0 { weight } else { 0.0 }).collect();
+        ColorPalette { colors, weights, mode, last: None }
+    }
+
+    /// Create a palette, where every color has the same weight
+    ///
+    /// # Input
+    /// * `colors`: The colors of the palette
+    /// * `mode`: How the colors are chosen
+    pub fn uniform(colors: Vec<Color>, mode: PaletteMode) -> Self {
+        let weights = vec![1.0; colors.len()];
+        ColorPalette::new(colors, weights, mode)
+    }
+
+    /// Choose the next color by the mode of the palette
+    ///
+    /// # Input
+    /// * `rng`: The random number generator to use
+    /// * `time_of_day`: How much of the day passed, for `PaletteMode::TimeOfDay`
+    ///
+    /// # Output
+    /// The chosen `Color`, or the fallback color if the palette is empty
+    pub fn next_color<R: Rng>(&mut self, rng: &mut R, time_of_day: Duration) -> Color {
+        if self.colors.is_empty() {
+            warn!("Using default color!");
+            return FALLBACK_COLOR;
+        }
+
+        let index = match self.mode {
+            PaletteMode::Random => self.choose_weighted(None, rng),
+            PaletteMode::NoRepeat => self.choose_weighted(self.last, rng),
+            PaletteMode::Sequential => self.last.map(|last| (last + 1) % self.colors.len()).unwrap_or(0),
+            PaletteMode::TimeOfDay => self.choose_by_time(time_of_day),
+        };
+
+        self.last = Some(index);
+        self.colors[index]
+    }
+
+    /// Choose a random index by the weights. Without any usable weight, every color is equally likely
+    ///
+    /// # Input
+    /// * `exclude`: An index that isn't to be chosen, unless it's the only color
+    /// * `rng`: The random number generator to use
+    fn choose_weighted<R: Rng>(&self, exclude: Option<usize>, rng: &mut R) -> usize {
+        let weights = self.weights.iter().enumerate()
+            .map(|(index, weight)| if Some(index) == exclude { 0.0 } else { *weight });
+
+        match WeightedIndex::new(weights) {
+            Ok(distribution) => distribution.sample(rng),
+            Err(_) => {
+                let others: Vec<usize> = (0..self.colors.len()).filter(|index| Some(*index) != exclude).collect();
+                *others.choose(rng).unwrap_or(&0)
+            }
+        }
+    }
+
+    /// Choose the index, whose share of the day contains the given time
+    ///
+    /// # Input
+    /// `time_of_day`: How much of the day passed
+    fn choose_by_time(&self, time_of_day: Duration) -> usize {
+        let weights: Vec<f32> = match self.weights.iter().sum::<f32>() {
+            total if total > 0.0 => self.weights.iter().map(|weight| weight / total).collect(),
+            _ => vec![1.0 / self.colors.len() as f32; self.colors.len()],
+        };
+
+        let mut position = (time_of_day.as_secs_f64() % DAY.as_secs_f64() / DAY.as_secs_f64()) as f32;
+        for (index, weight) in weights.iter().enumerate() {
+            if position < *weight {
+                return index;
+            }
+            position -= weight;
+        }
+
+        //Rounding errors can leave a rest at the end of the day
+        weights.iter().rposition(|weight| *weight > 0.0).unwrap_or(0)
+    }
+}
+
+/// Get how much of the current day passed
+///
+/// # Input
+/// `utc_offset`: How many hours the local time is ahead of UTC
+///
+/// # Output
+/// The `Duration` since midnight
+fn time_of_day(utc_offset: f32) -> Duration {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64();
+    let local = since_epoch + utc_offset as f64 * 60.0 * 60.0;
+    Duration::from_secs_f64(local.rem_euclid(DAY.as_secs_f64()))
+}
+
+/// Initialize the color palette of the settings with either
 /// * the given color from the argument
 /// * or the default color, if no argument was given
+///
+/// The mode of the arguments overwrites the one of the palette file
 pub fn init_color_palette(path: &Option<PathBuf>) {
-    let mut pal: Vec<Color> = Vec::new();
-
-    match path {
+    let pal = match path {
         None => {
             //Default palette
-            pal = DEFAULT_PALETTE.to_vec();
+            ColorPalette::uniform(DEFAULT_PALETTE.to_vec(), PaletteMode::default())
         }
         Some(pal_path) => {
             //Read color palette
             match read_color_palette(&PathBuf::from(pal_path)) {
-                Ok(p) => {
-                    pal = p;
+                Ok(p) => p,
+                Err(e) => {
+                    warn!("{}", e.to_string());
+                    ColorPalette::uniform(Vec::new(), PaletteMode::default())
                 }
-                Err(e) => { warn!("{}", e.to_string()) }
-            };
+            }
         }
+    };
+
+    set_palette(with_argument_mode(pal), path.clone());
+}
+
+/// Read a palette file and use it from now on
+///
+/// # Input
+/// `path`: The path to the color palette file
+///
+/// # Output
+/// A `Result<usize, ColorError>` with the count of colors, that fails when the file can't be read or is empty.
+/// The palette doesn't change then
+pub fn load_color_palette(path: &PathBuf) -> Result<usize, ColorError> {
+    let pal = with_argument_mode(read_color_palette(path)?);
+    let count = pal.colors.len();
+    set_palette(pal, Some(path.clone()));
+
+    Ok(count)
+}
+
+/// Read the palette file, that is used, again. This way changes of the file are used without a restart
+///
+/// # Output
+/// A `Result<usize, ColorError>` with the count of colors, that fails when the file can't be read or no palette
+/// file is used
+pub fn reload_color_palette() -> Result<usize, ColorError> {
+    match palette_path() {
+        None => Err(ColorError::Other(String::from("No palette file is used"))),
+        Some(path) => load_color_palette(&path),
     }
+}
 
-    COLOR_PALETTE.get_or_init(|| pal);
+/// Replace the colors of the palette, keeping its mode. Every color gets the same weight
+///
+/// # Input
+/// `colors`: The color strings of the new palette
+///
+/// # Output
+/// A `Result<usize, ColorError>` with the count of colors, that fails when a color is invalid or there's none.
+/// The palette doesn't change then
+pub fn set_palette_colors(colors: &[String]) -> Result<usize, ColorError> {
+    let colors = colors.iter()
+        .map(|color| Color::from_str(color).map_err(|e| ColorError::Other(format!("Invalid color ({}): {}", color, e))))
+        .collect::<Result<Vec<Color>, ColorError>>()?;
+    if colors.is_empty() {
+        return Err(ColorError::Other(String::from("No colors given")));
+    }
+
+    let mode = with_palette(|pal| pal.mode).unwrap_or_default();
+    let count = colors.len();
+    set_palette(with_argument_mode(ColorPalette::uniform(colors, mode)), None);
+
+    Ok(count)
+}
+
+/// Overwrite the mode of a palette with the one of the arguments, if set
+fn with_argument_mode(mut pal: ColorPalette) -> ColorPalette {
+    let binding = fallback_arguments();
+    let args = ARGUMENTS.get().unwrap_or(&binding);
+
+    if let Some(mode) = args.palette_mode {
+        pal.mode = mode;
+    }
+    pal
 }
 
 /// Read a given color palette file
@@ -151,59 +591,60 @@ pub fn init_color_palette(path: &Option<PathBuf>) {
 /// A `PathBuf` to the color palette file
 ///
 /// # Output
-/// A `Result<Vec<Color>, ColorError>`
-/// * The `Vec<Color>` is the list of colors, that are found in the color palette file
+/// A `Result<ColorPalette, ColorError>`
+/// * The `ColorPalette` has the colors, that are found in the color palette file, with their weights and the mode
 /// * A `ColorError` is thrown, when the file cannot be read or the palette is empty
-pub fn read_color_palette(path: &PathBuf) -> Result<Vec<Color>, ColorError> {
-    let mut palette: Vec<Color> = Vec::new();
+pub fn read_color_palette(path: &PathBuf) -> Result<ColorPalette, ColorError> {
+    let palette = read_palette_colors(path)?;
+    let mut colors: Vec<Color> = Vec::new();
+    let mut weights: Vec<f32> = Vec::new();
 
-    let pal = read_palette(path)?;
-    for hex_color in pal.colors {
-        match Color::from_hex_string(hex_color.as_str()) {
+    for entry in palette.colors {
+        match Color::from_str(entry.color()) {
             Ok(c) => {
                 info!("Added #{} to color palette", format!("{}", c).truecolor(c.r, c.g, c.b));
-                palette.push(c);
+                colors.push(c);
+                weights.push(entry.weight());
             }
             Err(e) => warn!("Problem with reading color: {}", e.to_string())
         };
     }
 
-    if palette.is_empty() {
+    if colors.is_empty() {
         Err(ColorError::Other(String::from("No colors in file")))
     } else {
-        Ok(palette)
+        Ok(ColorPalette::new(colors, weights, palette.mode))
     }
 }
 
-/// Get a random color from the current color palette
+/// Get the next color from the current color palette, chosen by the mode of the palette
 ///
 /// # Output
-/// A randomly selected `Color` from the color palette
-///
-/// # Todo
-/// Could be combined with `get_random_color()` using an `Option<>` for `colors: &Vec<Color>`
-pub fn get_random_color_from_palette() -> Color {
-    match COLOR_PALETTE.get() {
+/// The selected `Color` from the color palette
+pub fn get_color_from_palette() -> Color {
+    let binding = fallback_arguments();
+    let args = ARGUMENTS.get().unwrap_or(&binding);
+
+    let color = with_palette(|pal| with_rng(|rng| pal.next_color(rng, time_of_day(args.utc_offset))));
+    match color {
         None => {
             warn!("Can't get color palette! Reverting back to default palette!");
-            get_random_color(&DEFAULT_PALETTE.to_vec())
-        }
-        Some(pal) => {
-            get_random_color(pal)
+            with_rng(|rng| get_random_color(&DEFAULT_PALETTE.to_vec(), rng))
         }
+        Some(color) => color,
     }
 }
 
 /// Get a random color from a given color palette
 ///
 /// # Input
-/// A `Vec<Colo>` that's the color palette
+/// * `colors`: A `Vec<Color>` that's the color palette
+/// * `rng`: The random number generator to use
 ///
 /// # Output
 /// A random `Color` from the given color palette
-fn get_random_color(colors: &Vec<Color>) -> Color {
-    let mut rng = thread_rng();
-    match colors.choose(&mut rng) {
+fn get_random_color<R: Rng>(colors: &Vec<Color>, rng: &mut R) -> Color {
+    match colors.choose(rng) {
         None => {
             warn!("Using default color!");
             FALLBACK_COLOR
@@ -212,8 +653,8 @@ fn get_random_color(colors: &Vec<Color>) -> Color {
     }
 }
 
-/// Get the right color for the base and blink animations. Based on the given arguments,
-/// a few possibilities are valid:
+/// Get the right color for the base and blink animations. Based on the given arguments and the current
+/// settings, a few possibilities are valid:
 /// * use the color of the animation
 /// * use the overwrite color
 /// * use a random selected color
@@ -224,23 +665,11 @@ fn get_random_color(colors: &Vec<Color>) -> Color {
 /// # Output
 /// The right `Color` that should be use to render
 pub fn get_base_or_blink_color(use_rand_color: bool) -> Option<Color> {
-    let default_args = fallback_arguments();
-    let args = ARGUMENTS.get().unwrap_or(&default_args);
-
-    //Convert given color
-    let def_color = match u32::from_str_radix(&args.default_color.clone().unwrap_or(FALLBACK_COLOR.to_string()), 16) { //todo: improve this
-        Ok(c) => { c }
-        Err(_) => {
-            warn!("Can't parse given color. Using default color");
-            FALLBACK_COLOR.to_hex()
-        }
-    };
-
     //If default color set, use it, else keep the animations color
-    let color = if def_color != FALLBACK_COLOR.to_hex() { Some(Color::from_hex(def_color)) } else { None };
+    let color = default_color().filter(|def_color| *def_color != FALLBACK_COLOR);
 
     //However, also check, if a random color should be chosen. If not, use whatever the last line yielded
-    if use_rand_color { Some(get_random_color_from_palette()) } else { color }
+    if use_rand_color { Some(get_color_from_palette()) } else { color }
 }
 
 /// Calculate the gamma correction for a channel value
@@ -259,7 +688,9 @@ mod tests {
     use super::*;
 
     use std::path::PathBuf;
-    use rand::Rng;
+    use proptest::prelude::*;
+    use rand::rngs::StdRng;
+    use rand::{SeedableRng, thread_rng};
 
     #[test]
     fn test_to_hex() {
@@ -281,11 +712,30 @@ mod tests {
         let color = Color::from_hex_string(hex_string).unwrap();
         assert_eq!(color, Color { r: 255, g: 0, b: 0 });
 
-        let hex_string = "fff";
+        let hex_string = "#f08";
+        let color = Color::from_hex_string(hex_string).unwrap();
+        assert_eq!(color, Color { r: 255, g: 0, b: 136 });
+
+        let hex_string = "ffff";
         let result = Color::from_hex_string(hex_string);
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_from_str() {
+        let pink = Color { r: 255, g: 0, b: 128 };
+        assert_eq!(Color::from_str("FF0080"), Ok(pink));
+        assert_eq!(Color::from_str(" #ff0080 "), Ok(pink));
+        assert_eq!(Color::from_str("rgb(255, 0, 128)"), Ok(pink));
+        assert_eq!(Color::from_str("RGB(255 0 128)"), Ok(pink));
+        assert_eq!(Color::from_str("HotPink"), Ok(Color { r: 255, g: 105, b: 180 }));
+        assert_eq!(Color::from_str("#fff"), Ok(WHITE));
+
+        assert!(Color::from_str("rgb(256, 0, 0)").is_err());
+        assert!(Color::from_str("rgb(1, 2)").is_err());
+        assert!(Color::from_str("notacolor").is_err());
+    }
+
     #[test]
     fn test_to_raw() {
         let color = Color { r: 255, g: 0, b: 0 };
@@ -293,14 +743,93 @@ mod tests {
         assert_eq!(raw, [0, 0, 255, 0]);
     }
 
+    #[test]
+    fn test_blend() {
+        let gray = Color { r: 128, g: 128, b: 128 };
+        let orange = Color { r: 255, g: 128, b: 0 };
+
+        assert_eq!(WHITE.multiply(orange), orange);
+        assert_eq!(gray.multiply(orange), Color { r: 128, g: 64, b: 0 });
+        assert_eq!(BLACK.screen(orange), orange);
+        assert_eq!(gray.screen(orange), Color { r: 255, g: 192, b: 128 });
+        assert_eq!(Color { r: 64, g: 64, b: 64 }.overlay(orange), Color { r: 128, g: 64, b: 0 });
+        assert_eq!(gray.tint(orange, 0.5), Color { r: 192, g: 128, b: 64 });
+        assert_eq!(gray.tint(orange, 0.0), gray);
+
+        //Hue of red, saturation and value of dark cyan
+        assert_eq!(Color { r: 0, g: 128, b: 128 }.replace_hue(RED), Color { r: 128, g: 0, b: 0 });
+        assert_eq!(gray.replace_hue(RED), gray);
+    }
+
+    #[test]
+    fn test_hsv() {
+        for color in [RED, YELLOW, GREEN, CYAN, BLUE, PURPLE, WHITE, BLACK, Color { r: 12, g: 200, b: 99 }] {
+            let (h, s, v) = color.to_hsv();
+            assert_eq!(Color::from_hsv(h, s, v), color);
+        }
+        assert_eq!(CYAN.to_hsv(), (180.0, 1.0, 1.0));
+        assert_eq!(Color::from_hsv(-120.0, 1.0, 1.0), BLUE);
+    }
+
+    #[test]
+    fn test_color_math() {
+        assert_eq!(RED.rotate_hue(120.0), GREEN);
+        assert_eq!(RED.rotate_hue(-120.0), BLUE);
+
+        let (lightness, _, _) = WHITE.to_oklab();
+        assert!((lightness - 1.0).abs() < 0.001);
+
+        //Halfway between black and white is halfway in perceived lightness, not in the sRGB values
+        let gray = BLACK.mix(WHITE, 0.5);
+        assert!(gray.r == gray.g && gray.g == gray.b);
+        assert!((gray.to_oklab().0 - 0.5).abs() < 0.01);
+    }
+
+    /// Check if two colors differ by at most one in every channel, as the conversions round
+    fn is_close(a: Color, b: Color) -> bool {
+        a.r.abs_diff(b.r) <= 1 && a.g.abs_diff(b.g) <= 1 && a.b.abs_diff(b.b) <= 1
+    }
+
+    proptest! {
+        #[test]
+        fn prop_hsv_round_trip(r: u8, g: u8, b: u8) {
+            let color = Color { r, g, b };
+            let (h, s, v) = color.to_hsv();
+            prop_assert!(is_close(Color::from_hsv(h, s, v), color));
+        }
+
+        #[test]
+        fn prop_oklab_round_trip(r: u8, g: u8, b: u8) {
+            let color = Color { r, g, b };
+            let (l, a, b) = color.to_oklab();
+            prop_assert!(is_close(Color::from_oklab(l, a, b), color));
+        }
+
+        #[test]
+        fn prop_mix_ends(r1: u8, g1: u8, b1: u8, r2: u8, g2: u8, b2: u8) {
+            let from = Color { r: r1, g: g1, b: b1 };
+            let to = Color { r: r2, g: g2, b: b2 };
+            prop_assert!(is_close(from.mix(to, 0.0), from));
+            prop_assert!(is_close(from.mix(to, 1.0), to));
+        }
+
+        #[test]
+        fn prop_full_hue_rotation(r: u8, g: u8, b: u8) {
+            let color = Color { r, g, b };
+            prop_assert!(is_close(color.rotate_hue(360.0), color));
+        }
+    }
+
     #[test]
     fn test_read_color_palette() {
         let path = PathBuf::from("test_palette.json");
         let palette = read_color_palette(&path).unwrap();
-        assert_eq!(palette.len(), 3);
-        assert_eq!(palette[0], Color { r: 255, g: 0, b: 0 });
-        assert_eq!(palette[1], Color { r: 0, g: 255, b: 0 });
-        assert_eq!(palette[2], Color { r: 0, g: 0, b: 255 });
+        assert_eq!(palette.colors.len(), 3);
+        assert_eq!(palette.colors[0], Color { r: 255, g: 0, b: 0 });
+        assert_eq!(palette.colors[1], Color { r: 0, g: 255, b: 0 });
+        assert_eq!(palette.colors[2], Color { r: 0, g: 0, b: 255 });
+        assert_eq!(palette.weights, vec![1.0, 1.0, 1.0]);
+        assert_eq!(palette.mode, PaletteMode::Random);
     }
 
     /*
@@ -322,7 +851,7 @@ mod tests {
     #[test]
     fn test_get_random_color_from_palette_with_palette() {
         let mock_palette = mock_palette();
-        let color = get_random_color(&mock_palette);
+        let color = get_random_color(&mock_palette, &mut thread_rng());
 
         assert!(mock_palette.contains(&color));
     }
@@ -331,13 +860,54 @@ mod tests {
     fn test_get_random_color_from_palette_same_color_multiple_calls() {
         let mock_palette = mock_palette();
 
-        let color_a = get_random_color(&mock_palette);
-        let color_b = get_random_color(&mock_palette);
+        let color_a = get_random_color(&mock_palette, &mut thread_rng());
+        let color_b = get_random_color(&mock_palette, &mut thread_rng());
 
         assert!(mock_palette.contains(&color_a));
         assert!(mock_palette.contains(&color_b));
     }
 
+    #[test]
+    fn test_get_random_color_seeded() {
+        let mock_palette = mock_palette();
+        let mut rng_a = StdRng::seed_from_u64(42);
+        let mut rng_b = StdRng::seed_from_u64(42);
+
+        let colors_a: Vec<Color> = (0..10).map(|_| get_random_color(&mock_palette, &mut rng_a)).collect();
+        let colors_b: Vec<Color> = (0..10).map(|_| get_random_color(&mock_palette, &mut rng_b)).collect();
+        assert_eq!(colors_a, colors_b);
+    }
+
+    #[test]
+    fn test_next_color() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let hour = Duration::from_secs(60 * 60);
+
+        let mut weighted = ColorPalette::new(mock_palette(), vec![0.0, -1.0, 2.0], PaletteMode::Random);
+        assert!((0..10).all(|_| weighted.next_color(&mut rng, hour) == BLUE));
+
+        let mut no_repeat = ColorPalette::uniform(mock_palette(), PaletteMode::NoRepeat);
+        let colors: Vec<Color> = (0..20).map(|_| no_repeat.next_color(&mut rng, hour)).collect();
+        assert!(colors.windows(2).all(|pair| pair[0] != pair[1]));
+
+        let mut single = ColorPalette::uniform(vec![RED], PaletteMode::NoRepeat);
+        assert_eq!(single.next_color(&mut rng, hour), RED);
+        assert_eq!(single.next_color(&mut rng, hour), RED);
+
+        let mut sequential = ColorPalette::uniform(mock_palette(), PaletteMode::Sequential);
+        let colors: Vec<Color> = (0..4).map(|_| sequential.next_color(&mut rng, hour)).collect();
+        assert_eq!(colors, vec![RED, GREEN, BLUE, RED]);
+
+        let mut time_of_day = ColorPalette::new(mock_palette(), vec![1.0, 1.0, 2.0], PaletteMode::TimeOfDay);
+        assert_eq!(time_of_day.next_color(&mut rng, hour), RED);
+        assert_eq!(time_of_day.next_color(&mut rng, hour * 6), GREEN);
+        assert_eq!(time_of_day.next_color(&mut rng, hour * 23), BLUE);
+        assert_eq!(time_of_day.next_color(&mut rng, hour * 25), RED);
+
+        let mut empty = ColorPalette::uniform(Vec::new(), PaletteMode::Sequential);
+        assert_eq!(empty.next_color(&mut rng, hour), FALLBACK_COLOR);
+    }
+
     #[test]
     fn test_get_gamma_correction() {
         let channel_value = 128;
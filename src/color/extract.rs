@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+
+use crate::color::{BLACK, Color, ColorError};
+use crate::file_operations::Palette;
+use crate::gif::{Animation, read_animation};
+use crate::library::Library;
+
+/// How close two colors can be in OKLab, before the less dominant one counts as duplicate
+const MIN_DISTANCE: f32 = 0.08;
+
+/// Colors with a lower OKLab lightness are skipped like black, as they hardly show on the LEDs
+const MIN_LIGHTNESS: f32 = 0.15;
+
+/// How many more boxes the median cut creates than colors are wanted, so there are some left when duplicates
+/// are skipped
+const OVERSAMPLING: usize = 2;
+
+/// A box of the median cut, containing colors with the count of their pixels
+struct ColorBox {
+    colors: Vec<(Color, u32)>,
+}
+
+impl ColorBox {
+    /// The count of pixels in the box
+    fn population(&self) -> u64 {
+        self.colors.iter().map(|(_, count)| *count as u64).sum()
+    }
+
+    /// Get the channel with the widest range
+    ///
+    /// # Output
+    /// The index of the channel (0 for red, 1 for green and 2 for blue) and its range
+    fn widest_channel(&self) -> (usize, u8) {
+        (0..3)
+            .map(|channel| {
+                let values = self.colors.iter().map(|(color, _)| channel_of(color, channel));
+                let range = values.clone().max().unwrap_or(0) - values.min().unwrap_or(0);
+                (channel, range)
+            })
+            .max_by_key(|(_, range)| *range)
+            .unwrap_or((0, 0))
+    }
+
+    /// Split the box at the median pixel of its widest channel
+    ///
+    /// # Output
+    /// Both halves, or `None` if the box has a single color
+    fn split(mut self) -> Option<(ColorBox, ColorBox)> {
+        if self.colors.len() < 2 {
+            return None;
+        }
+
+        let (channel, _) = self.widest_channel();
+        self.colors.sort_by_key(|(color, _)| channel_of(color, channel));
+
+        //Split where half of the pixels are on each side, but keep at least one color per half
+        let half = self.population() / 2;
+        let mut seen = 0;
+        let mut index = 0;
+        while index < self.colors.len() - 1 && seen + (self.colors[index].1 as u64) <= half {
+            seen += self.colors[index].1 as u64;
+            index += 1;
+        }
+        let index = index.clamp(1, self.colors.len() - 1);
+
+        let upper = self.colors.split_off(index);
+        Some((self, ColorBox { colors: upper }))
+    }
+
+    /// The average color of the box, weighted by the count of pixels
+    fn average(&self) -> Color {
+        let population = self.population().max(1);
+        let channel = |channel: usize| {
+            let sum: u64 = self.colors.iter().map(|(color, count)| channel_of(color, channel) as u64 * *count as u64).sum();
+            ((sum as f64 / population as f64).round()) as u8
+        };
+
+        Color { r: channel(0), g: channel(1), b: channel(2) }
+    }
+}
+
+/// Get a channel of a color by its index
+fn channel_of(color: &Color, channel: usize) -> u8 {
+    match channel {
+        0 => color.r,
+        1 => color.g,
+        _ => color.b,
+    }
+}
+
+/// Get the perceived distance between two colors
+fn distance(a: Color, b: Color) -> f32 {
+    let (l1, a1, b1) = a.to_oklab();
+    let (l2, a2, b2) = b.to_oklab();
+    ((l1 - l2).powi(2) + (a1 - a2).powi(2) + (b1 - b2).powi(2)).sqrt()
+}
+
+/// Find the dominant colors of animations by median cut. Black, colors close to it and near-duplicates of
+/// more dominant colors are skipped
+///
+/// # Input
+/// * `anims`: The animations to analyze
+/// * `count`: How many colors are wanted at most
+///
+/// # Output
+/// The colors, the most dominant first
+pub fn extract_colors(anims: &[Animation], count: usize) -> Vec<Color> {
+    let mut histogram: HashMap<u32, u32> = HashMap::new();
+    for pixel in anims.iter().flat_map(|anim| &anim.frames).flat_map(|frame| frame.pixels.iter().flatten()) {
+        let color = Color { r: pixel.r, g: pixel.g, b: pixel.b };
+        if color != BLACK {
+            *histogram.entry(color.to_hex()).or_insert(0) += 1;
+        }
+    }
+
+    //Split the box with the most pixels, that still has a range, until there are enough boxes
+    let mut boxes = vec![ColorBox { colors: histogram.into_iter().map(|(hex, count)| (Color::from_hex(hex), count)).collect() }];
+    while boxes.len() < count * OVERSAMPLING {
+        let splittable = boxes.iter().enumerate()
+            .filter(|(_, color_box)| color_box.widest_channel().1 > 0)
+            .max_by_key(|(_, color_box)| color_box.population())
+            .map(|(index, _)| index);
+
+        let Some(index) = splittable else { break };
+        match boxes.swap_remove(index).split() {
+            Some((lower, upper)) => boxes.extend([lower, upper]),
+            None => break,
+        }
+    }
+
+    //The most dominant colors are kept, when near-duplicates are skipped
+    boxes.retain(|color_box| !color_box.colors.is_empty());
+    boxes.sort_by_key(|color_box| std::cmp::Reverse(color_box.population()));
+
+    let mut colors: Vec<Color> = Vec::new();
+    for color in boxes.iter().map(|color_box| color_box.average()) {
+        if colors.len() >= count {
+            break;
+        }
+        if color.to_oklab().0 >= MIN_LIGHTNESS && colors.iter().all(|other| distance(color, *other) >= MIN_DISTANCE) {
+            colors.push(color);
+        }
+    }
+
+    colors
+}
+
+/// Extract a palette from animations and write it as JSON palette
+///
+/// # Input
+/// * `paths`: Paths to animations or directories, that are searched recursively
+/// * `output`: The path of the palette file
+/// * `count`: How many colors are wanted at most
+/// * `ignore_patterns`: Patterns of files and folders in directories, that are to ignore
+///
+/// # Output
+/// A `Result<Vec<Color>, ColorError>` with the colors of the palette, that fails when no color was found or the
+/// palette can't be written. Animations that can't be read are skipped
+pub fn extract_palette(paths: &[PathBuf], output: &Path, count: usize, ignore_patterns: &[String]) -> Result<Vec<Color>, ColorError> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            match Library::scan(path, ignore_patterns) {
+                Ok(library) => files.extend(library.entries.into_iter().map(|entry| entry.path)),
+                Err(e) => warn!("Can't read directory ({}): {}", path.display(), e),
+            }
+        } else {
+            files.push(path.clone());
+        }
+    }
+
+    let anims: Vec<Animation> = files.iter()
+        .filter_map(|file| match read_animation(file) {
+            Ok(anim) => Some(anim),
+            Err(e) => {
+                warn!("Skip animation, that can't be read ({}): {}", file.display(), e);
+                None
+            }
+        })
+        .collect();
+    info!("Extracting palette from {} animation/animations", anims.len());
+
+    let colors = extract_colors(&anims, count);
+    if colors.is_empty() {
+        return Err(ColorError::Other(String::from("No colors found in the animations")));
+    }
+
+    let palette = Palette::from_colors(colors.iter().map(|color| color.to_string()).collect());
+    fs::write(output, serde_json::to_string_pretty(&palette)?)?;
+
+    Ok(colors)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::{BLUE, RED, WHITE};
+    use crate::gif::fixtures::striped_animation;
+
+    use super::*;
+
+    #[test]
+    fn test_extract_colors() {
+        let almost_red = Color { r: 250, g: 2, b: 0 };
+        let almost_black = Color { r: 3, g: 3, b: 3 };
+        let anim = striped_animation(&[RED, RED, RED, almost_red, BLUE, BLUE, WHITE, almost_black]);
+
+        let colors = extract_colors(&[anim], 4);
+        assert_eq!(colors.len(), 3);
+        assert!(distance(colors[0], RED) < MIN_DISTANCE);
+        assert!(colors.contains(&BLUE));
+        assert!(colors.contains(&WHITE));
+
+        assert_eq!(extract_colors(&[striped_animation(&[BLUE, BLUE, WHITE])], 1), vec![BLUE]);
+        assert!(extract_colors(&[striped_animation(&[])], 4).is_empty());
+    }
+}
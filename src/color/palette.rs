@@ -0,0 +1,225 @@
+use std::fs;
+use std::path::Path;
+
+use log::warn;
+
+use crate::color::ColorError;
+use crate::file_operations::{Palette, read_palette};
+
+/// The magic number of Adobe Swatch Exchange files
+const ASE_MAGIC: &[u8] = b"ASEF";
+
+/// Block of an Adobe Swatch Exchange file, that contains a color
+const ASE_BLOCK_COLOR: u16 = 0x0001;
+
+/// The first line of GIMP palettes
+const GPL_HEADER: &str = "GIMP Palette";
+
+/// Read a palette file. The format is detected by the content and the extension:
+/// * Adobe Swatch Exchange (`.ase`)
+/// * GIMP palette (`.gpl`)
+/// * Lospec hex palette (`.hex`)
+/// * Paint.NET palette (`.txt`)
+/// * our own JSON palette otherwise
+///
+/// # Input
+/// `path`: The path to the palette file
+///
+/// # Output
+/// A `Result<Palette, ColorError>` with all colors as strings, that fails when the file can't be read. Only our own
+/// JSON palettes have weights and a mode
+pub fn read_palette_colors(path: &Path) -> Result<Palette, ColorError> {
+    let data = fs::read(path)?;
+    let text = String::from_utf8_lossy(&data);
+    let extension = path.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+
+    if data.starts_with(ASE_MAGIC) {
+        read_ase(&data).map(Palette::from_colors)
+    } else if text.starts_with(GPL_HEADER) {
+        read_gpl(&text).map(Palette::from_colors)
+    } else if extension == "hex" {
+        Ok(Palette::from_colors(read_hex(&text)))
+    } else if extension == "txt" {
+        Ok(Palette::from_colors(read_paint_net(&text)))
+    } else {
+        Ok(read_palette(&path.to_path_buf())?)
+    }
+}
+
+/// Read a GIMP palette. Every color is a line with the decimal channels, optionally followed by a name
+fn read_gpl(text: &str) -> Result<Vec<String>, ColorError> {
+    let mut colors: Vec<String> = Vec::new();
+
+    for line in text.lines().skip(1).map(|line| line.trim()) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with("Name:") || line.starts_with("Columns:") {
+            continue;
+        }
+
+        let channels: Vec<u8> = line.split_whitespace().take(3).filter_map(|channel| channel.parse().ok()).collect();
+        match channels[..] {
+            [r, g, b] => colors.push(format!("{:02X}{:02X}{:02X}", r, g, b)),
+            _ => return Err(ColorError::Palette(format!("Invalid line in GIMP palette: {}", line))),
+        }
+    }
+
+    Ok(colors)
+}
+
+/// Read a Lospec hex palette, which is a hex color per line
+fn read_hex(text: &str) -> Vec<String> {
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_owned())
+        .collect()
+}
+
+/// Read a Paint.NET palette. Every color is a line in `AARRGGBB` hex, comments start with `;`. The alpha is ignored
+fn read_paint_net(text: &str) -> Vec<String> {
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with(';'))
+        .map(|line| match line.get(2..) {
+            //Lines with other characters than hex digits are kept, so they fail as invalid color
+            Some(rgb) if line.len() == 8 => rgb.to_owned(),
+            _ => line.to_owned(),
+        })
+        .collect()
+}
+
+/// Read an Adobe Swatch Exchange file. RGB, CMYK and gray colors are supported, LAB colors are skipped
+fn read_ase(data: &[u8]) -> Result<Vec<String>, ColorError> {
+    let mut reader = ByteReader::new(data);
+    reader.skip(ASE_MAGIC.len() + 4)?; //version
+    let block_count = reader.u32()?;
+
+    let mut colors: Vec<String> = Vec::new();
+    for _ in 0..block_count {
+        let block_type = reader.u16()?;
+        let length = reader.u32()? as usize;
+        let block = reader.bytes(length)?;
+        if block_type != ASE_BLOCK_COLOR {
+            continue;
+        }
+
+        let mut block = ByteReader::new(block);
+        let name_length = block.u16()? as usize;
+        block.skip(name_length * 2)?; //UTF-16 name
+        let model = block.bytes(4)?;
+
+        let (r, g, b) = match model {
+            b"RGB " => (block.f32()?, block.f32()?, block.f32()?),
+            b"Gray" => {
+                let gray = block.f32()?;
+                (gray, gray, gray)
+            }
+            b"CMYK" => {
+                let (c, m, y, k) = (block.f32()?, block.f32()?, block.f32()?, block.f32()?);
+                ((1.0 - c) * (1.0 - k), (1.0 - m) * (1.0 - k), (1.0 - y) * (1.0 - k))
+            }
+            _ => {
+                warn!("Skip color of unsupported model ({}) in ASE palette", String::from_utf8_lossy(model).trim());
+                continue;
+            }
+        };
+
+        let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+        colors.push(format!("{:02X}{:02X}{:02X}", channel(r), channel(g), channel(b)));
+    }
+
+    Ok(colors)
+}
+
+/// Reads big endian values from a byte slice, as used by Adobe Swatch Exchange files
+struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        ByteReader { data, pos: 0 }
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], ColorError> {
+        match self.data.get(self.pos..self.pos + count) {
+            None => Err(ColorError::Palette("Unexpected end of ASE file".to_owned())),
+            Some(bytes) => {
+                self.pos += count;
+                Ok(bytes)
+            }
+        }
+    }
+
+    fn skip(&mut self, count: usize) -> Result<(), ColorError> {
+        self.bytes(count).map(|_| ())
+    }
+
+    fn u16(&mut self) -> Result<u16, ColorError> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, ColorError> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn f32(&mut self) -> Result<f32, ColorError> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    /// Create a color block of an ASE file
+    fn ase_block(model: &[u8; 4], values: &[f32]) -> Vec<u8> {
+        let mut block: Vec<u8> = Vec::new();
+        block.extend(2u16.to_be_bytes()); //name length with terminator
+        block.extend([0, b'a', 0, 0]);
+        block.extend(model);
+        values.iter().for_each(|value| block.extend(value.to_be_bytes()));
+        block.extend(0u16.to_be_bytes()); //color type
+
+        let mut data: Vec<u8> = Vec::new();
+        data.extend(ASE_BLOCK_COLOR.to_be_bytes());
+        data.extend((block.len() as u32).to_be_bytes());
+        data.extend(block);
+        data
+    }
+
+    #[test]
+    fn test_read_palette_colors() {
+        let temp_dir = TempDir::new("test_read_palette_colors").unwrap();
+        let dir = temp_dir.path();
+
+        fs::write(dir.join("gimp.gpl"), "GIMP Palette\nName: Test\nColumns: 2\n# comment\n255   0 128\tPink\n  0 255   0\n").unwrap();
+        fs::write(dir.join("lospec.hex"), "ff0080\n00ff00\n\n").unwrap();
+        fs::write(dir.join("paint.txt"), "; Paint.NET Palette File\nFFFF0080\nFF00FF00\n").unwrap();
+
+        let mut ase: Vec<u8> = Vec::new();
+        ase.extend(ASE_MAGIC);
+        ase.extend([0, 1, 0, 0]);
+        ase.extend(3u32.to_be_bytes());
+        ase.extend(ase_block(b"RGB ", &[1.0, 0.0, 0.5]));
+        ase.extend(ase_block(b"LAB ", &[50.0, 0.0, 0.0]));
+        ase.extend(ase_block(b"CMYK", &[1.0, 0.0, 1.0, 0.0]));
+        fs::write(dir.join("adobe.ase"), ase).unwrap();
+
+        let colors = |name: &str| read_palette_colors(&dir.join(name)).unwrap().colors.iter().map(|entry| entry.color().to_owned()).collect::<Vec<String>>();
+        assert_eq!(colors("gimp.gpl"), vec!["FF0080", "00FF00"]);
+        assert_eq!(colors("lospec.hex"), vec!["ff0080", "00ff00"]);
+        assert_eq!(colors("paint.txt"), vec!["FF0080", "00FF00"]);
+        assert_eq!(colors("adobe.ase"), vec!["FF0080", "00FF00"]);
+
+        assert_eq!(read_paint_net("FFFF0080\naé12345\n"), vec!["FF0080", "aé12345"]);
+
+        fs::write(dir.join("broken.gpl"), "GIMP Palette\n255 0\n").unwrap();
+        assert!(read_palette_colors(&dir.join("broken.gpl")).is_err());
+        assert!(read_palette_colors(&dir.join("missing.hex")).is_err());
+    }
+}
@@ -0,0 +1,351 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use log::warn;
+
+use crate::arguments::{ARGUMENTS, fallback_arguments};
+use crate::color::{BLACK, Color, WHITE};
+use crate::gif::{Animation, Frame, Pixel, pixel_is_black, pixel_is_grayscale};
+
+#[derive(clap::ValueEnum, Copy, Clone, PartialEq, Debug)]
+/// How the gray levels of an animation are mapped onto the overwrite color
+pub enum ColorMapping {
+    /// Every pixel, that isn't black, gets the overwrite color
+    Flat,
+
+    /// The overwrite color is scaled by the luminance of the pixel, from black to the full color
+    Luminance,
+
+    /// The luminance of the pixel picks a color of a gradient. Without gradient stops, the gradient goes from
+    /// black over the overwrite color to white
+    Gradient,
+}
+
+impl Display for ColorMapping {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ColorMapping::Flat => "flat",
+            ColorMapping::Luminance => "luminance",
+            ColorMapping::Gradient => "gradient",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(clap::ValueEnum, Copy, Clone, PartialEq, Debug)]
+/// What has to be grayscale to get the overwrite color. Everything else keeps its own colors
+pub enum ColorizeScope {
+    /// Only animations, whose frames are all grayscale
+    Animation,
+
+    /// Every grayscale frame, even if other frames of the animation have colors
+    Frame,
+
+    /// Every gray pixel, so colored accents of an animation are kept
+    Pixel,
+}
+
+impl Display for ColorizeScope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ColorizeScope::Animation => "animation",
+            ColorizeScope::Frame => "frame",
+            ColorizeScope::Pixel => "pixel",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(clap::ValueEnum, Copy, Clone, PartialEq, Debug)]
+/// How the overwrite color is blended with the colors of an animation. Unlike the `ColorMapping`, this
+/// works for colored animations as well
+pub enum BlendMode {
+    /// Darken the animation by the color
+    Multiply,
+
+    /// Brighten the animation by the color
+    Screen,
+
+    /// Multiply dark and screen bright parts of the animation
+    Overlay,
+
+    /// Use the hue of the color, but keep saturation and value of the animation
+    Hue,
+
+    /// Mix the color into the animation by the tint amount
+    Tint,
+}
+
+impl Display for BlendMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            BlendMode::Multiply => "multiply",
+            BlendMode::Screen => "screen",
+            BlendMode::Overlay => "overlay",
+            BlendMode::Hue => "hue",
+            BlendMode::Tint => "tint",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+/// Maps the pixels of an animation onto an overwrite color
+pub struct ColorMapper {
+    /// The `ColorMapping` that is used
+    mapping: ColorMapping,
+
+    /// The stops of the gradient, evenly spread from dark to bright
+    stops: Vec<Color>,
+
+    /// The `BlendMode`, that replaces the mapping, if set
+    blend: Option<BlendMode>,
+
+    /// How much of the color `BlendMode::Tint` mixes in, between 0.0 and 1.0
+    tint: f32,
+
+    /// What has to be grayscale to get the overwrite color
+    scope: ColorizeScope,
+}
+
+impl ColorMapper {
+    /// Create a new mapper
+    ///
+    /// # Input
+    /// * `mapping`: The `ColorMapping` to use
+    /// * `stops`: The stops of the gradient. Fewer than two stops use the default gradient
+    pub fn new(mapping: ColorMapping, stops: Vec<Color>) -> Self {
+        ColorMapper { mapping, stops, blend: None, tint: 0.5, scope: ColorizeScope::Animation }
+    }
+
+    /// Blend the overwrite color with the pixels instead of mapping their gray levels
+    ///
+    /// # Input
+    /// * `blend`: The `BlendMode` to use
+    /// * `tint`: How much of the color `BlendMode::Tint` mixes in, between 0.0 and 1.0
+    pub fn with_blend(mut self, blend: BlendMode, tint: f32) -> Self {
+        self.blend = Some(blend);
+        self.tint = tint;
+        self
+    }
+
+    /// Set what has to be grayscale to get the overwrite color
+    pub fn with_scope(mut self, scope: ColorizeScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// Check if the mapper blends, so colored animations can get an overwrite color as well
+    pub fn blends(&self) -> bool {
+        self.blend.is_some()
+    }
+
+    /// Check if any part of an animation is grayscale within the `ColorizeScope`, so it gets the overwrite color
+    /// without blending
+    pub fn colorizes(&self, anim: &Animation) -> bool {
+        match self.scope {
+            ColorizeScope::Animation => anim.grayscale,
+            ColorizeScope::Frame => anim.frames.iter().any(|frame| frame.grayscale),
+            ColorizeScope::Pixel => anim.frames.iter()
+                .flat_map(|frame| frame.pixels.iter().flatten())
+                .any(|pixel| !pixel_is_black(pixel) && pixel_is_grayscale(pixel)),
+        }
+    }
+
+    /// Create a mapper from the arguments. Gradient stops that can't be parsed are skipped
+    pub fn from_arguments() -> Self {
+        let binding = fallback_arguments();
+        let args = ARGUMENTS.get().unwrap_or(&binding);
+
+        let stops = args.gradient_stops.iter()
+            .filter_map(|stop| match Color::from_str(stop) {
+                Ok(color) => Some(color),
+                Err(e) => {
+                    warn!("Skip invalid gradient stop ({}): {}", stop, e);
+                    None
+                }
+            })
+            .collect();
+
+        let mapper = ColorMapper::new(args.color_mapping, stops).with_scope(args.colorize_scope);
+        match args.blend_mode {
+            None => mapper,
+            Some(blend) => mapper.with_blend(blend, args.tint as f32 / 100.0),
+        }
+    }
+
+    /// Map a pixel onto the overwrite color, or blend them if a `BlendMode` is set. Black pixels always
+    /// stay black, as they are the background
+    ///
+    /// # Input
+    /// * `pixel`: The `Pixel` of the animation
+    /// * `color`: The overwrite `Color`
+    ///
+    /// # Output
+    /// The `Color` the pixel is shown with
+    pub fn map(&self, pixel: &Pixel, color: &Color) -> Color {
+        if pixel_is_black(pixel) {
+            return BLACK;
+        }
+
+        if let Some(blend) = self.blend {
+            let own = Color { r: pixel.r, g: pixel.g, b: pixel.b };
+            return match blend {
+                BlendMode::Multiply => own.multiply(*color),
+                BlendMode::Screen => own.screen(*color),
+                BlendMode::Overlay => own.overlay(*color),
+                BlendMode::Hue => own.replace_hue(*color),
+                BlendMode::Tint => own.tint(*color, self.tint),
+            };
+        }
+
+        let luminance = luminance(pixel);
+        match self.mapping {
+            ColorMapping::Flat => *color,
+            ColorMapping::Luminance => scale(color, luminance),
+            ColorMapping::Gradient if self.stops.len() >= 2 => gradient(&self.stops, luminance),
+            ColorMapping::Gradient => gradient(&[BLACK, *color, WHITE], luminance),
+        }
+    }
+
+    /// Map a pixel onto the overwrite color like `map()`, but only if the pixel is grayscale within the
+    /// `ColorizeScope`. Blending applies to every pixel
+    ///
+    /// # Input
+    /// * `frame`: The `Frame` the pixel belongs to
+    /// * `pixel`: The `Pixel` of the frame
+    /// * `color`: The overwrite `Color`
+    ///
+    /// # Output
+    /// The `Color` the pixel is shown with
+    pub fn colorize(&self, frame: &Frame, pixel: &Pixel, color: &Color) -> Color {
+        let recolor = self.blends() || match self.scope {
+            ColorizeScope::Animation => true,
+            ColorizeScope::Frame => frame.grayscale,
+            ColorizeScope::Pixel => pixel_is_grayscale(pixel),
+        };
+
+        if recolor {
+            self.map(pixel, color)
+        } else {
+            Color { r: pixel.r, g: pixel.g, b: pixel.b }
+        }
+    }
+}
+
+/// Get the perceived brightness of a pixel
+///
+/// # Output
+/// The luminance between 0.0 and 1.0
+fn luminance(pixel: &Pixel) -> f32 {
+    (0.299 * pixel.r as f32 + 0.587 * pixel.g as f32 + 0.114 * pixel.b as f32) / 255.0
+}
+
+/// Scale the channels of a color, so it gets darker
+///
+/// # Input
+/// * `color`: The color at 1.0
+/// * `factor`: The brightness between 0.0 (black) and 1.0
+fn scale(color: &Color, factor: f32) -> Color {
+    let factor = factor.clamp(0.0, 1.0);
+    let channel = |c: u8| (c as f32 * factor).round() as u8;
+
+    Color { r: channel(color.r), g: channel(color.g), b: channel(color.b) }
+}
+
+/// Pick a color of a gradient, whose stops are evenly spread between 0.0 and 1.0. The stops are mixed perceptually
+///
+/// # Input
+/// * `stops`: At least two colors
+/// * `t`: The position within the gradient
+fn gradient(stops: &[Color], t: f32) -> Color {
+    let position = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+    let index = (position.floor() as usize).min(stops.len() - 2);
+
+    stops[index].mix(stops[index + 1], position - index as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gif::fixtures;
+
+    use super::*;
+
+    fn gray(value: u8) -> Pixel {
+        Pixel { r: value, g: value, b: value, a: 255 }
+    }
+
+    #[test]
+    fn test_map() {
+        let color = Color { r: 200, g: 100, b: 0 };
+
+        let flat = ColorMapper::new(ColorMapping::Flat, Vec::new());
+        assert_eq!(flat.map(&gray(51), &color), color);
+        assert_eq!(flat.map(&gray(0), &color), BLACK);
+
+        let luminance = ColorMapper::new(ColorMapping::Luminance, Vec::new());
+        assert_eq!(luminance.map(&gray(255), &color), color);
+        assert_eq!(luminance.map(&gray(51), &color), Color { r: 40, g: 20, b: 0 });
+        assert_eq!(luminance.map(&gray(0), &color), BLACK);
+
+        let default_gradient = ColorMapper::new(ColorMapping::Gradient, Vec::new());
+        assert_eq!(default_gradient.map(&gray(255), &color), WHITE);
+        assert_eq!(default_gradient.map(&gray(128), &color), Color { r: 200, g: 101, b: 3 });
+
+        let stops = vec![Color { r: 0, g: 0, b: 255 }, Color { r: 255, g: 0, b: 0 }];
+        let gradient = ColorMapper::new(ColorMapping::Gradient, stops);
+        assert_eq!(gradient.map(&gray(255), &color), Color { r: 255, g: 0, b: 0 });
+        assert_eq!(gradient.map(&gray(1), &color), Color { r: 2, g: 4, b: 254 });
+    }
+
+    #[test]
+    fn test_map_blend() {
+        let color = Color { r: 255, g: 0, b: 0 };
+        let pixel = Pixel { r: 0, g: 128, b: 255, a: 255 };
+
+        let tint = ColorMapper::new(ColorMapping::Flat, Vec::new()).with_blend(BlendMode::Tint, 0.25);
+        assert!(tint.blends());
+        assert_eq!(tint.map(&pixel, &color), Color { r: 64, g: 96, b: 191 });
+        assert_eq!(tint.map(&gray(0), &color), BLACK);
+
+        let multiply = ColorMapper::new(ColorMapping::Gradient, Vec::new()).with_blend(BlendMode::Multiply, 0.0);
+        assert_eq!(multiply.map(&pixel, &color), BLACK);
+        assert_eq!(multiply.map(&gray(255), &color), color);
+    }
+
+    #[test]
+    fn test_colorize() {
+        let color = Color { r: 255, g: 0, b: 0 };
+        let heart = Pixel { r: 255, g: 0, b: 128, a: 255 };
+
+        //An eye in gray with a colored heart in the second frame
+        let eye = Frame::from_pixels([[gray(200); 28]; 8], 10);
+        let mut pixels = eye.pixels;
+        pixels[0][0] = heart;
+        let with_heart = Frame::from_pixels(pixels, 10);
+        let anim = fixtures::animation(vec![eye.clone(), with_heart.clone()]);
+
+        let animation = ColorMapper::new(ColorMapping::Flat, Vec::new());
+        assert!(!animation.colorizes(&anim));
+        assert_eq!(animation.colorize(&with_heart, &gray(200), &color), color);
+
+        let frame = animation.clone().with_scope(ColorizeScope::Frame);
+        assert!(frame.colorizes(&anim));
+        assert_eq!(frame.colorize(&eye, &gray(200), &color), color);
+        assert_eq!(frame.colorize(&with_heart, &gray(200), &color), Color { r: 200, g: 200, b: 200 });
+        assert_eq!(frame.colorize(&with_heart, &heart, &color), Color { r: 255, g: 0, b: 128 });
+
+        let pixel = animation.clone().with_scope(ColorizeScope::Pixel);
+        assert!(pixel.colorizes(&anim));
+        assert_eq!(pixel.colorize(&with_heart, &gray(200), &color), color);
+        assert_eq!(pixel.colorize(&with_heart, &heart, &color), Color { r: 255, g: 0, b: 128 });
+        assert_eq!(pixel.colorize(&with_heart, &gray(0), &color), BLACK);
+
+        //Blending applies to all pixels
+        let blend = pixel.with_blend(BlendMode::Multiply, 0.0);
+        assert_eq!(blend.colorize(&with_heart, &heart, &color), color);
+
+        let colored = fixtures::animation(vec![with_heart]);
+        assert!(!frame.colorizes(&colored));
+    }
+}
@@ -0,0 +1,330 @@
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::path::Path;
+
+use gif::{DisposalMethod, Encoder, Repeat};
+use log::info;
+use png::{BitDepth, ColorType};
+use thiserror::Error;
+
+use crate::gif::{Animation, Frame, GifError, read_animation};
+use crate::renderer::tasbot_eyes::{NUM_PIXELS, PIXEL_POSITIONS, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+/// Size of the global palette of a GIF
+const GIF_PALETTE_SIZE: usize = 256;
+
+/// Speed of the color quantization, when an animation has too many colors for a single GIF palette.
+/// 1 is the best quality, 30 the fastest
+const GIF_QUANTIZATION_SPEED: i32 = 10;
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("An IO error occurred: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Can't read animation: {0}")]
+    Read(#[from] GifError),
+
+    #[error("A GIF encoder error occurred: {0}")]
+    GifEncode(#[from] gif::EncodingError),
+
+    #[error("A PNG encoder error occurred: {0}")]
+    PngEncode(#[from] png::EncodingError),
+}
+
+#[derive(clap::ValueEnum, Copy, Clone, PartialEq, Debug)]
+/// The formats an animation can be exported to
+pub enum ExportFormat {
+    /// An optimized GIF
+    Gif,
+
+    /// A directory with a PNG file for every frame
+    Png,
+
+    /// A C header with the frames in the LED order of TASBots display
+    CHeader,
+
+    /// Like the C header, but with the data stored in the flash of Arduino compatible boards
+    Arduino,
+}
+
+/// Read an animation and export it
+///
+/// # Input
+/// * `input`: The path to the animation
+/// * `output`: The path of the file (or directory for PNG frames) that is to write
+/// * `format`: The `ExportFormat` to use
+/// * `name`: The name of the arrays in C headers. Uses the file name of the output, if not set
+pub fn export_from_path(input: &Path, output: &Path, format: ExportFormat, name: Option<&str>) -> Result<(), ExportError> {
+    let anim = read_animation(&input.to_path_buf())?;
+
+    match format {
+        ExportFormat::Gif => export_gif(&anim, output)?,
+        ExportFormat::Png => export_png_frames(&anim, output)?,
+        ExportFormat::CHeader | ExportFormat::Arduino => {
+            let file_name = output.file_stem().and_then(|s| s.to_str()).unwrap_or("animation");
+            let header = to_c_header(&anim, name.unwrap_or(file_name), format == ExportFormat::Arduino);
+            fs::write(output, header)?;
+        }
+    }
+
+    info!("Exported ({}) to ({})", input.display(), output.display());
+    Ok(())
+}
+
+/// Write an animation as GIF. Identical frames in a row are merged and every frame only contains
+/// the area that changed since the previous frame
+///
+/// # Input
+/// * `anim`: The `Animation` that is to write
+/// * `path`: The path of the GIF file
+pub fn export_gif(anim: &Animation, path: &Path) -> Result<(), ExportError> {
+    //Merge identical frames in a row
+    let mut frames: Vec<Frame> = Vec::new();
+    for frame in &anim.frames {
+        match frames.last_mut() {
+            Some(last) if last.pixels == frame.pixels => last.delay = last.delay.saturating_add(frame.delay),
+            _ => frames.push(frame.clone()),
+        }
+    }
+
+    //Use a single exact palette, if the colors fit in
+    let mut palette: Vec<[u8; 3]> = Vec::new();
+    for pixel in frames.iter().flat_map(|f| f.pixels.iter().flatten()) {
+        let color = [pixel.r, pixel.g, pixel.b];
+        if !palette.contains(&color) {
+            palette.push(color);
+        }
+    }
+    let exact_palette = palette.len() <= GIF_PALETTE_SIZE;
+    let global_palette: Vec<u8> = if exact_palette { palette.concat() } else { Vec::new() };
+
+    let mut encoder = Encoder::new(File::create(path)?, SCREEN_WIDTH as u16, SCREEN_HEIGHT as u16, &global_palette)?;
+    encoder.set_repeat(Repeat::Infinite)?;
+
+    let mut previous: Option<&Frame> = None;
+    for frame in &frames {
+        let (left, top, width, height) = match previous {
+            None => (0, 0, SCREEN_WIDTH, SCREEN_HEIGHT),
+            Some(previous) => changed_area(previous, frame),
+        };
+
+        let mut gif_frame = if exact_palette {
+            let mut indices: Vec<u8> = Vec::with_capacity(width * height);
+            for row in &frame.pixels[top..top + height] {
+                for pixel in &row[left..left + width] {
+                    let index = palette.iter().position(|c| *c == [pixel.r, pixel.g, pixel.b]).unwrap_or(0);
+                    indices.push(index as u8);
+                }
+            }
+
+            gif::Frame {
+                width: width as u16,
+                height: height as u16,
+                buffer: Cow::Owned(indices),
+                ..gif::Frame::default()
+            }
+        } else {
+            let mut rgb: Vec<u8> = Vec::with_capacity(width * height * 3);
+            for row in &frame.pixels[top..top + height] {
+                for pixel in &row[left..left + width] {
+                    rgb.extend([pixel.r, pixel.g, pixel.b]);
+                }
+            }
+            gif::Frame::from_rgb_speed(width as u16, height as u16, &rgb, GIF_QUANTIZATION_SPEED)
+        };
+
+        gif_frame.left = left as u16;
+        gif_frame.top = top as u16;
+        gif_frame.delay = frame.delay;
+        gif_frame.dispose = DisposalMethod::Keep;
+        encoder.write_frame(&gif_frame)?;
+
+        previous = Some(frame);
+    }
+
+    Ok(())
+}
+
+/// Get the smallest area, that contains all pixels that differ between two frames
+///
+/// # Input
+/// * `previous`: The frame shown before
+/// * `current`: The frame shown now
+///
+/// # Output
+/// A tuple of the left, top, width and height of the area. It's at least a single pixel big
+fn changed_area(previous: &Frame, current: &Frame) -> (usize, usize, usize, usize) {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (SCREEN_WIDTH, SCREEN_HEIGHT, 0, 0);
+
+    for y in 0..SCREEN_HEIGHT {
+        for x in 0..SCREEN_WIDTH {
+            let (a, b) = (previous.pixels[y][x], current.pixels[y][x]);
+            if (a.r, a.g, a.b) != (b.r, b.g, b.b) {
+                min_x = min_x.min(x);
+                min_y = min_y.min(y);
+                max_x = max_x.max(x);
+                max_y = max_y.max(y);
+            }
+        }
+    }
+
+    if min_x > max_x {
+        (0, 0, 1, 1)
+    } else {
+        (min_x, min_y, max_x - min_x + 1, max_y - min_y + 1)
+    }
+}
+
+/// Write every frame of an animation as PNG file into a directory. The files are named
+/// `frame_000.png`, `frame_001.png` and so on
+///
+/// # Input
+/// * `anim`: The `Animation` that is to write
+/// * `dir`: The directory the PNG files are written to. It's created if it doesn't exist
+pub fn export_png_frames(anim: &Animation, dir: &Path) -> Result<(), ExportError> {
+    fs::create_dir_all(dir)?;
+
+    for (i, frame) in anim.frames.iter().enumerate() {
+        let rgb: Vec<u8> = frame.pixels.iter()
+            .flatten()
+            .flat_map(|p| [p.r, p.g, p.b])
+            .collect();
+
+        let file = File::create(dir.join(format!("frame_{:03}.png", i)))?;
+        let mut encoder = png::Encoder::new(file, SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32);
+        encoder.set_color(ColorType::Rgb);
+        encoder.set_depth(BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&rgb)?;
+    }
+
+    Ok(())
+}
+
+/// Convert an animation to a C header. The frames are arrays of `0x00RRGGBB` values in the LED
+/// order of TASBots display (see `PIXEL_POSITIONS`), so firmware can copy them to the LEDs directly
+///
+/// # Input
+/// * `anim`: The `Animation` that is to convert
+/// * `name`: The name used for the arrays and defines
+/// * `arduino`: If the arrays should be stored in the flash memory (`PROGMEM`) of Arduino compatible boards
+///
+/// # Output
+/// The content of the header file
+pub fn to_c_header(anim: &Animation, name: &str, arduino: bool) -> String {
+    let name = to_c_identifier(name);
+    let define = name.to_uppercase();
+    let progmem = if arduino { " PROGMEM" } else { "" };
+
+    let mut result = String::new();
+    result.push_str(&format!("// Animation \"{}\" exported by tasbot-eyes\n", name));
+    result.push_str("#pragma once\n\n");
+    result.push_str(if arduino { "#include <Arduino.h>\n\n" } else { "#include <stdint.h>\n\n" });
+    result.push_str(&format!("#define {}_FRAME_COUNT {}\n", define, anim.frames.len()));
+    result.push_str(&format!("#define {}_LED_COUNT {}\n\n", define, NUM_PIXELS));
+
+    //Delays in milliseconds
+    let delays: Vec<String> = anim.frames.iter().map(|f| (f.delay as u32 * 10).to_string()).collect();
+    result.push_str("// Delay of every frame in milliseconds\n");
+    result.push_str(&format!("const uint16_t {}_delays[{}_FRAME_COUNT]{} = {{{}}};\n\n", name, define, progmem, delays.join(", ")));
+
+    //Frames in LED order
+    result.push_str("// Colors of every LED as 0x00RRGGBB\n");
+    result.push_str(&format!("const uint32_t {}_frames[{}_FRAME_COUNT][{}_LED_COUNT]{} = {{\n", name, define, define, progmem));
+    for frame in &anim.frames {
+        let mut leds = vec![0u32; NUM_PIXELS as usize];
+        for (y, row) in PIXEL_POSITIONS.iter().enumerate() {
+            for (x, index) in row.iter().enumerate() {
+                if let Some(index) = index {
+                    let p = frame.pixels[y][x];
+                    leds[*index] = ((p.r as u32) << 16) | ((p.g as u32) << 8) | (p.b as u32);
+                }
+            }
+        }
+
+        let values: Vec<String> = leds.iter().map(|v| format!("0x{:08X}", v)).collect();
+        result.push_str("    {\n");
+        for chunk in values.chunks(8) {
+            result.push_str(&format!("        {},\n", chunk.join(", ")));
+        }
+        result.push_str("    },\n");
+    }
+    result.push_str("};\n");
+
+    result
+}
+
+/// Convert a name to a valid C identifier by replacing all other characters with underscores
+fn to_c_identifier(name: &str) -> String {
+    let mut identifier: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+
+    if identifier.is_empty() || identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        identifier.insert(0, '_');
+    }
+    identifier
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use crate::color::{BLUE, RED};
+    use crate::gif::fixtures::animation;
+
+    use super::*;
+
+    /// Two identical frames with a red pixel, followed by one with another blue pixel
+    fn mock_animation() -> Animation {
+        let first = Frame::dotted(&[(2, 0, RED)], 10);
+        animation(vec![first.clone(), first, Frame::dotted(&[(2, 0, RED), (25, 7, BLUE)], 20)])
+    }
+
+    #[test]
+    fn test_export_gif() {
+        let temp_dir = TempDir::new("test_export_gif").unwrap();
+        let path = temp_dir.path().join("export.gif");
+        let anim = mock_animation();
+
+        export_gif(&anim, &path).unwrap();
+        let read = read_animation(&path).unwrap();
+
+        //The identical frames are merged
+        assert_eq!(read.frames.len(), 2);
+        assert_eq!(read.frames[0].delay, 20);
+        assert_eq!(read.frames[1].delay, 20);
+        assert_eq!(read.frames[1].pixels[0][2].r, 255);
+        assert_eq!(read.frames[1].pixels[7][25].b, 255);
+    }
+
+    #[test]
+    fn test_export_png_frames() {
+        let temp_dir = TempDir::new("test_export_png_frames").unwrap();
+        let dir = temp_dir.path().join("frames");
+
+        export_png_frames(&mock_animation(), &dir).unwrap();
+        let read = read_animation(&dir.join("frame_002.png")).unwrap();
+
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 3);
+        assert_eq!(read.frames[0].pixels[7][25].b, 255);
+    }
+
+    #[test]
+    fn test_changed_area() {
+        let anim = mock_animation();
+        assert_eq!(changed_area(&anim.frames[0], &anim.frames[1]), (0, 0, 1, 1));
+        assert_eq!(changed_area(&anim.frames[1], &anim.frames[2]), (25, 7, 1, 1));
+    }
+
+    #[test]
+    fn test_to_c_header() {
+        let header = to_c_header(&mock_animation(), "3 eyes", true);
+
+        assert!(header.contains("#define _3_EYES_FRAME_COUNT 3"));
+        assert!(header.contains("#define _3_EYES_LED_COUNT 154"));
+        assert!(header.contains("const uint16_t _3_eyes_delays[_3_EYES_FRAME_COUNT] PROGMEM = {100, 100, 200};"));
+        //The pixel at (2, 0) is the very first LED
+        assert!(header.contains("        0x00FF0000, 0x00000000"));
+    }
+}
@@ -1,10 +1,15 @@
 use std::{fs, io};
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
-use log::error;
+use log::{error, warn};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::color::PaletteMode;
+use crate::gif::sprite_sheet::is_sprite_sheet_file;
+use crate::playback::Playback;
+
 /// The path to the base animation
 pub const BASE_PATH: &str = "./gifs/base.gif";
 
@@ -17,6 +22,9 @@ pub const OTHER_PATH: &str = "./gifs/others/";
 /// The path to the directory, with all the blink animations
 pub const BLINK_PATH: &str = "./gifs/blinks/";
 
+/// The path to the directory with the palettes, that network clients can load
+pub const PALETTE_PATH: &str = "./palettes/";
+
 #[derive(Error, Debug)]
 pub enum FileOperationsError {
     #[error("An IO error occurred: {0}")]
@@ -29,8 +37,42 @@ pub struct Playlist {
     ///Indicates that a JSON file contains a playlist
     data_type: String,
 
-    ///Paths as strings to the animation that should get played in given order
-    pub entries: Vec<String>,
+    ///Paths as strings to the animation that should get played in given order, optionally with playback modifiers
+    pub entries: Vec<PlaylistEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(untagged)]
+/// An entry of a playlist, which is either just the path or the path with playback modifiers
+pub enum PlaylistEntry {
+    ///Path as string to the animation
+    Path(String),
+
+    ///Path as string to the animation and how it's to play
+    Detailed {
+        path: String,
+
+        #[serde(flatten)]
+        playback: Playback,
+    },
+}
+
+impl PlaylistEntry {
+    /// The path to the animation
+    pub fn path(&self) -> &str {
+        match self {
+            PlaylistEntry::Path(path) => path,
+            PlaylistEntry::Detailed { path, .. } => path,
+        }
+    }
+
+    /// The playback modifiers of the entry
+    pub fn playback(&self) -> Playback {
+        match self {
+            PlaylistEntry::Path(_) => Playback::default(),
+            PlaylistEntry::Detailed { playback, .. } => playback.clone(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -39,8 +81,166 @@ pub struct Palette {
     ///Indicates that a JSON file contains a palette
     data_type: String,
 
-    ///Entries are hex color strings
-    pub colors: Vec<String>,
+    ///Entries are color strings, optionally with a weight
+    pub colors: Vec<PaletteEntry>,
+
+    ///How the colors are chosen from the palette
+    #[serde(default)]
+    pub mode: PaletteMode,
+}
+
+impl Palette {
+    /// Create a palette of equally weighted colors, that are chosen randomly
+    ///
+    /// # Input
+    /// `colors`: The color strings of the palette
+    pub fn from_colors(colors: Vec<String>) -> Self {
+        Palette {
+            data_type: String::from("palette"),
+            colors: colors.into_iter().map(PaletteEntry::Color).collect(),
+            mode: PaletteMode::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(untagged)]
+/// An entry of a palette, which is either just the color or the color with a weight
+pub enum PaletteEntry {
+    ///Color string
+    Color(String),
+
+    ///Color string and how often it's chosen compared to the other colors
+    Weighted {
+        color: String,
+        weight: f32,
+    },
+}
+
+impl PaletteEntry {
+    /// The color string of the entry
+    pub fn color(&self) -> &str {
+        match self {
+            PaletteEntry::Color(color) => color,
+            PaletteEntry::Weighted { color, .. } => color,
+        }
+    }
+
+    /// The weight of the entry. Entries without one have a weight of 1.0
+    pub fn weight(&self) -> f32 {
+        match self {
+            PaletteEntry::Color(_) => 1.0,
+            PaletteEntry::Weighted { weight, .. } => *weight,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+/// Only the `data_type` of a JSON file, to tell playlists, palettes and sprite sheets apart
+pub struct DataType {
+    ///What the JSON file contains, e.g. `playlist`, `palette` or `sprite_sheet`
+    pub data_type: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Structure JSON sprite sheet descriptors get parsed to
+pub struct SpriteSheet {
+    ///Indicates that a JSON file describes a sprite sheet
+    pub data_type: String,
+
+    ///Path to the PNG file with all frames, relative to the descriptor
+    pub image: String,
+
+    ///Width of a single frame in pixels
+    pub frame_width: u32,
+
+    ///Height of a single frame in pixels
+    pub frame_height: u32,
+
+    ///Count of frames in a row of the sheet. Uses as many as fit into the image, if not set
+    #[serde(default)]
+    pub columns: Option<u32>,
+
+    ///Count of rows of the sheet. Uses as many as fit into the image, if not set
+    #[serde(default)]
+    pub rows: Option<u32>,
+
+    ///Pixels between the border of the image and the frames
+    #[serde(default)]
+    pub margin: u32,
+
+    ///Pixels between two frames
+    #[serde(default)]
+    pub spacing: u32,
+
+    ///Indices of the frames in the order they are played, counted row by row. Plays all frames, if not set
+    #[serde(default)]
+    pub order: Option<Vec<usize>>,
+
+    ///Delays in milliseconds. Either a single one for all frames or one for every frame in `order`
+    #[serde(default)]
+    pub delays: Vec<u32>,
+
+    ///How often the frames are played in a row
+    #[serde(default)]
+    pub loop_count: Option<u16>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+/// What happens on the display after an animation is done
+pub enum Transition {
+    ///The last frame stays until the next animation starts
+    Cut,
+
+    ///The display gets cleared
+    Clear,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+/// Structure optional JSON sidecar files next to animations get parsed to. All fields are optional
+pub struct Metadata {
+    ///Free form tags to group animations
+    pub tags: Vec<String>,
+
+    ///How likely the animation gets chosen, relative to others. 0 never chooses it
+    pub weight: f32,
+
+    ///How often the animation is played in a row. At least once
+    pub repetitions: u16,
+
+    ///Indicates, if the color of the animation may get overwritten
+    pub allow_color_overwrite: bool,
+
+    ///Hex color string, that is always used for the animation
+    pub color: Option<String>,
+
+    ///What to do after the animation is done
+    pub transition: Transition,
+
+    ///Minimum frame delay in milliseconds, that replaces the frame delay policy of the arguments.
+    ///0 keeps all delays as they are
+    pub min_frame_delay: Option<u32>,
+
+    ///How the frames of the animation are played, including its speed
+    #[serde(flatten)]
+    pub playback: Playback,
+}
+
+impl Default for Metadata {
+    fn default() -> Self {
+        Metadata {
+            tags: Vec::new(),
+            weight: 1.0,
+            repetitions: 1,
+            allow_color_overwrite: true,
+            color: None,
+            transition: Transition::Cut,
+            min_frame_delay: None,
+            playback: Playback::default(),
+        }
+    }
 }
 
 /// Read the given palette at the given path into a `Palette`
@@ -83,6 +283,51 @@ pub fn read_playlist(path: &PathBuf) -> Result<Playlist, serde_json::error::Erro
     }
 }
 
+/// Read the metadata sidecar file (`<name>.json`) of an animation
+///
+/// # Input
+/// A `Path` to the animation
+///
+/// # Output
+/// The `Metadata` of the animation. If there's no sidecar file or it can't be read, the default
+/// metadata is returned. A sprite sheet descriptor next to its image is no sidecar file
+pub fn read_metadata(path: &Path) -> Metadata {
+    let sidecar = path.with_extension("json");
+    if sidecar == path || !sidecar.is_file() || is_sprite_sheet_file(&sidecar) {
+        return Metadata::default();
+    }
+
+    let metadata = fs::read_to_string(&sidecar)
+        .map_err(|e| e.to_string())
+        .and_then(|data| serde_json::from_str::<Metadata>(&data).map_err(|e| e.to_string()));
+
+    match metadata {
+        Ok(mut metadata) => {
+            //An animation, that never plays, would just be skipped silently
+            if metadata.repetitions == 0 {
+                warn!("Repetitions of 0 in metadata ({}), the animation is played once instead", sidecar.display());
+                metadata.repetitions = 1;
+            }
+            metadata
+        }
+        Err(e) => {
+            warn!("Can't read metadata ({}): {}", sidecar.display(), e);
+            Metadata::default()
+        }
+    }
+}
+
+/// Get when a file was modified the last time
+///
+/// # Input
+/// A `Path` to the file
+///
+/// # Output
+/// The modification time, `None` if the file doesn't exist or the time can't be read
+pub fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
 /// List all files in a given directory
 ///
 /// # Input
@@ -141,22 +386,90 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn test_read_metadata() {
+        let temp_dir = TempDir::new("test_read_metadata").unwrap();
+        let dir = temp_dir.path();
+
+        let anim = dir.join("anim.gif");
+        let other = dir.join("other.gif");
+        let broken = dir.join("broken.gif");
+        let never = dir.join("never.gif");
+        fs::File::create(&anim).unwrap();
+        fs::File::create(&other).unwrap();
+        fs::File::create(&broken).unwrap();
+        fs::File::create(&never).unwrap();
+        fs::write(dir.join("anim.json"), r#"{"tags": ["happy"], "weight": 2.0, "allow_color_overwrite": false, "transition": "clear", "reverse": true, "speed": 1.5}"#).unwrap();
+        fs::write(dir.join("broken.json"), "{").unwrap();
+        fs::write(dir.join("never.json"), r#"{"repetitions": 0}"#).unwrap();
+
+        let metadata = read_metadata(&anim);
+        assert_eq!(metadata.tags, vec!["happy"]);
+        assert_eq!(metadata.weight, 2.0);
+        assert_eq!(metadata.playback.speed, Some(1.5));
+        assert_eq!(metadata.repetitions, 1);
+        assert!(!metadata.allow_color_overwrite);
+        assert_eq!(metadata.transition, Transition::Clear);
+        assert!(metadata.playback.reverse);
+
+        assert_eq!(read_metadata(&other), Metadata::default());
+        assert_eq!(read_metadata(&broken), Metadata::default());
+        assert_eq!(read_metadata(&never).repetitions, 1);
+    }
+
+    #[test]
+    fn test_read_metadata_of_sprite_sheet() {
+        let temp_dir = TempDir::new("test_read_metadata_of_sprite_sheet").unwrap();
+        let dir = temp_dir.path();
+
+        //The descriptor is named like its image, so it looks like a sidecar file
+        fs::File::create(dir.join("sheet.png")).unwrap();
+        fs::write(dir.join("sheet.json"), r#"{"data_type": "sprite_sheet", "image": "sheet.png", "frame_width": 28, "frame_height": 8, "repetitions": 3}"#).unwrap();
+
+        assert_eq!(read_metadata(&dir.join("sheet.png")), Metadata::default());
+    }
+
     #[test]
     fn test_read_palette_ok() {
         let read_palette = read_palette(&PathBuf::from("test_palette.json")).unwrap();
         assert_eq!(read_palette.data_type, "palette");
-        assert_eq!(read_palette.colors, vec!["FF0000", "00FF00", "0000FF"]);
+        assert_eq!(read_palette.colors.iter().map(PaletteEntry::color).collect::<Vec<&str>>(), vec!["FF0000", "00FF00", "0000FF"]);
+    }
+
+    #[test]
+    fn test_read_palette_with_weights() {
+        let temp_dir = TempDir::new("test_read_palette_with_weights").unwrap();
+        let path = temp_dir.path().join("palette.json");
+        fs::write(&path, r#"{"data_type": "palette", "mode": "no_repeat", "colors": ["FF0000", {"color": "00FF00", "weight": 3}]}"#).unwrap();
+
+        let palette = read_palette(&path).unwrap();
+        assert_eq!(palette.mode, PaletteMode::NoRepeat);
+        assert_eq!(palette.colors.iter().map(PaletteEntry::color).collect::<Vec<&str>>(), vec!["FF0000", "00FF00"]);
+        assert_eq!(palette.colors.iter().map(|entry| entry.weight()).collect::<Vec<f32>>(), vec![1.0, 3.0]);
+        assert_eq!(read_palette(&PathBuf::from("test_palette.json")).unwrap().mode, PaletteMode::Random);
     }
 
     #[test]
     fn test_read_playlist_ok() {
         let read_playlist = read_playlist(&PathBuf::from("test_playlist.json")).unwrap();
         assert_eq!(read_playlist.data_type, "playlist");
-        assert_eq!(read_playlist.entries, vec![
+        assert_eq!(read_playlist.entries.iter().map(PlaylistEntry::path).collect::<Vec<&str>>(), vec![
             "./gifs/others/coin eyes.gif",
             "./gifs/others/colorful.gif",
             "./gifs/others/loading.gif",
             "./gifs/others/portal_eyes.gif",
         ]);
     }
+
+    #[test]
+    fn test_read_playlist_with_playback() {
+        let temp_dir = TempDir::new("test_read_playlist_with_playback").unwrap();
+        let path = temp_dir.path().join("playlist.json");
+        fs::write(&path, r#"{"data_type": "playlist", "entries": ["open.gif", {"path": "open.gif", "reverse": true, "hold_last": 500}]}"#).unwrap();
+
+        let playlist = read_playlist(&path).unwrap();
+        assert_eq!(playlist.entries[0], PlaylistEntry::Path("open.gif".to_owned()));
+        assert_eq!(playlist.entries[1].path(), "open.gif");
+        assert_eq!(playlist.entries[1].playback(), Playback { reverse: true, hold_last: 500, ..Default::default() });
+    }
 }
\ No newline at end of file
@@ -1,10 +1,34 @@
 use std::fmt::{Display, Formatter};
-use std::fs::File;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-use log::info;
+use log::{info, warn};
 use thiserror::Error;
 
+use crate::color::Color;
+
+///Decoder for animated (and still) PNG files
+pub mod apng;
+
+///Decoder for animated (and still) WebP files
+pub mod webp;
+
+///Decoder for Aseprite files
+pub mod aseprite;
+
+///Human editable text format for animations
+pub mod text;
+
+///Sprite sheets with a JSON descriptor
+pub mod sprite_sheet;
+
+///Rotating the palette colors of indexed animations
+pub mod cycle;
+
+///Test animations and a renderer, that the tests of all modules share
+#[cfg(test)]
+pub mod fixtures;
+
 /// The required height of an animation
 const HEIGHT: usize = 8;
 
@@ -18,8 +42,95 @@ pub enum GifError {
 
     #[error("A decoder error occurred: {0}")]
     Decode(#[from] gif::DecodingError),
+
+    #[error("A PNG decoder error occurred: {0}")]
+    Png(#[from] png::DecodingError),
+
+    #[error("A WebP decoder error occurred: {0}")]
+    WebP(#[from] image_webp::DecodingError),
+
+    #[error("An Aseprite error occurred: {0}")]
+    Aseprite(String),
+
+    #[error("An error in the text animation occurred: {0}")]
+    Text(String),
+
+    #[error("A JSON error occurred: {0}")]
+    JSON(#[from] serde_json::Error),
+
+    #[error("An error in the sprite sheet occurred: {0}")]
+    SpriteSheet(String),
+
+    #[error("Unsupported animation format")]
+    UnknownFormat,
+
+    #[error("Animation is {0}x{1}, but must be {WIDTH}x{HEIGHT}")]
+    Dimensions(u32, u32),
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+/// The file formats an `Animation` can be read from
+pub enum AnimationFormat {
+    /// GIF87a or GIF89a
+    Gif,
+
+    /// PNG, including animated PNG (APNG)
+    Png,
+
+    /// WebP, including animated WebP
+    WebP,
+
+    /// Aseprite files (`.aseprite` or `.ase`)
+    Aseprite,
+
+    /// Our own text format, see `text::read_text()`
+    Text,
+
+    /// A JSON descriptor of a PNG sprite sheet, see `file_operations::SpriteSheet`
+    SpriteSheet,
+}
+
+impl AnimationFormat {
+    /// Detect the format of an animation by the magic bytes at the start of the file. JSON is only a sprite
+    /// sheet descriptor, if its `data_type` says so, as metadata, palettes and playlists are JSON as well
+    ///
+    /// # Input
+    /// The first bytes (or all bytes) of the file. JSON needs all bytes
+    ///
+    /// # Output
+    /// The detected `AnimationFormat` or `None`, if the format is unknown
+    pub fn detect(data: &[u8]) -> Option<AnimationFormat> {
+        if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            Some(AnimationFormat::Gif)
+        } else if data.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+            Some(AnimationFormat::Png)
+        } else if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+            Some(AnimationFormat::WebP)
+        } else if data.len() >= 6 && data[4..6] == [0xE0, 0xA5] {
+            Some(AnimationFormat::Aseprite)
+        } else if data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data).starts_with(text::TEXT_MAGIC.as_bytes()) {
+            Some(AnimationFormat::Text)
+        } else if sprite_sheet::is_sprite_sheet(data) {
+            Some(AnimationFormat::SpriteSheet)
+        } else {
+            None
+        }
+    }
+
+    /// The usual file extension of the format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "gif",
+            AnimationFormat::Png => "png",
+            AnimationFormat::WebP => "webp",
+            AnimationFormat::Aseprite => "aseprite",
+            AnimationFormat::Text => "txt",
+            AnimationFormat::SpriteSheet => "json",
+        }
+    }
 }
 
+#[derive(Clone, Debug)]
 /// Structure that represents all the needed data from a GIF file
 pub struct Animation {
     /// All frames the GIF animation contains
@@ -29,9 +140,13 @@ pub struct Animation {
     /// If theres just a single pixel at any frame, that is not grayscale,
     /// the entire `Animation` counts as not grayscale anymore
     pub grayscale: bool,
+
+    /// The global palette of the GIF, that the palette indices of the frames point into. Only set, if all
+    /// frames use it
+    pub palette: Option<Vec<Color>>,
 }
 
-#[derive(Eq, PartialEq, Debug)]
+#[derive(Clone, Eq, PartialEq, Debug)]
 ///A single frame of an animation with its delay
 pub struct Frame {
     /// 2D array of all pixel of that frame
@@ -40,6 +155,14 @@ pub struct Frame {
     /// The delay of the frame, meaning, the duration *this* frame is shown.
     /// GIF frame delay are a multiple of 10 ms
     pub delay: u16,
+
+    /// 2D array of the palette index of every pixel, `None` where nothing was drawn. Only set for
+    /// animations with a palette
+    pub indices: Option<[[Option<u8>; WIDTH]; HEIGHT]>,
+
+    /// Indicates if all pixel of *this* frame are grayscale, so it can be colorized on its own,
+    /// even if other frames of the animation have colors
+    pub grayscale: bool,
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
@@ -81,7 +204,9 @@ impl Display for Frame {
     }
 }
 
-/// Read an animation from the given `PathBuf`
+/// Read an animation from the given `PathBuf`. The format is detected by the magic bytes
+/// of the file, so the file extension doesn't matter. For Aseprite files, a tag can be selected
+/// by appending it to the path, e.g. `eyes.aseprite#blink`
 ///
 /// # Input
 /// The `PathBuf` where the animation is stored
@@ -91,6 +216,7 @@ impl Display for Frame {
 /// * `Animation` being the successfully read animation
 /// * `GifError` being thrown when:
 ///     - The file cannot be opened
+///     - The format of the file is unknown
 ///     - The file info cannot be read
 ///     - Any frame cannot be read
 ///
@@ -99,48 +225,184 @@ impl Display for Frame {
 pub fn read_animation(path: &PathBuf) -> Result<Animation, GifError> {
     info!("Attempt to read ({})", path.to_str().unwrap_or("Invalid path"));
 
-    //Setup decoder
+    //Open and read file
+    let (path, tag) = split_tag(path);
+    let data = fs::read(&path)?;
+
+    let format = AnimationFormat::detect(&data);
+    if tag.is_some() && format != Some(AnimationFormat::Aseprite) {
+        warn!("Tags are only supported by Aseprite files, ignoring it");
+    }
+
+    let anim = match format {
+        Some(AnimationFormat::Gif) => read_gif(&data)?,
+        Some(AnimationFormat::Png) => apng::read_apng(&data)?,
+        Some(AnimationFormat::WebP) => webp::read_webp(&data)?,
+        Some(AnimationFormat::Aseprite) => aseprite::read_aseprite(&data, tag.as_deref())?,
+        Some(AnimationFormat::Text) => match std::str::from_utf8(&data) {
+            Ok(text) => text::read_text(text.trim_start_matches('\u{FEFF}'))?,
+            Err(_) => return Err(GifError::Text("File is not valid UTF-8".to_owned())),
+        },
+        Some(AnimationFormat::SpriteSheet) => sprite_sheet::read_sprite_sheet(&data, path.parent().unwrap_or(Path::new(".")))?,
+        None => return Err(GifError::UnknownFormat),
+    };
+
+    info!("Read animation - Frame count: {}, Grayscale: {}", anim.frames.len(), anim.grayscale);
+    Ok(anim)
+}
+
+/// Split an optional tag from a path, e.g. `eyes.aseprite#blink`. Existing files are never split,
+/// so file names that contain a `#` still work
+///
+/// # Input
+/// The path that might contain a tag
+///
+/// # Output
+/// A tuple of the actual path and the optional tag
+pub fn split_tag(path: &Path) -> (PathBuf, Option<String>) {
+    if path.exists() {
+        return (path.to_path_buf(), None);
+    }
+
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    match file_name.rsplit_once('#') {
+        None => (path.to_path_buf(), None),
+        Some((name, tag)) => (path.with_file_name(name), Some(tag.to_owned())),
+    }
+}
+
+/// Read an animation from the bytes of a GIF file
+///
+/// # Input
+/// The content of the GIF file
+///
+/// # Output
+/// A `Result<Animation, GifError>`, that fails when the file info or any frame cannot be read or the GIF
+/// is not as big as TASBots display
+fn read_gif(data: &[u8]) -> Result<Animation, GifError> {
+    //Setup decoder. The palette indices are kept, so the colors can be cycled
     let mut decoder = gif::DecodeOptions::new();
-    decoder.set_color_output(gif::ColorOutput::RGBA);
+    decoder.set_color_output(gif::ColorOutput::Indexed);
+    let mut decoder = decoder.read_info(data)?;
 
-    //Open and read file
-    let file = File::open(path)?;
-    let mut decoder = decoder.read_info(file)?;
+    let (width, height) = (decoder.width(), decoder.height());
+    if width as usize != WIDTH || height as usize != HEIGHT {
+        return Err(GifError::Dimensions(width as u32, height as u32));
+    }
+    let global_palette: Option<Vec<u8>> = decoder.global_palette().map(|palette| palette.to_vec());
 
     //Setup structure
     let mut anim: Animation = Animation {
         frames: vec![],
         grayscale: true,
+        palette: global_palette.as_deref().map(colors_of_palette),
     };
 
-    //Interpret data
+    //Interpret data. Frames might only cover a part of the screen, so they're drawn onto a canvas
+    let mut canvas = vec![0u8; WIDTH * HEIGHT * 4];
+    let mut indices: Vec<Option<u8>> = vec![None; WIDTH * HEIGHT];
     while let Some(raw_frame) = decoder.read_next_frame()? {
-        let frame = read_frame(raw_frame);
+        //Indices into a local palette don't match the global one
+        if raw_frame.palette.is_some() {
+            anim.palette = None;
+        }
+
+        let palette = raw_frame.palette.as_deref().or(global_palette.as_deref()).unwrap_or_default();
+        let frame = read_frame(raw_frame, palette, &mut canvas, &mut indices);
 
         //A single frame with color is worth enough to mark the entire animation as colorful
         if anim.grayscale {
-            anim.grayscale = frame_is_grayscale(&frame);
+            anim.grayscale = frame.grayscale;
         }
 
         anim.frames.push(frame);
     }
 
-    info!("Read animation - Frame count: {}, Grayscale: {}", anim.frames.len(), anim.grayscale);
+    if anim.palette.is_none() {
+        anim.frames.iter_mut().for_each(|frame| frame.indices = None);
+    }
+
     Ok(anim)
 }
 
+/// Convert the RGB triples of a GIF palette to colors
+fn colors_of_palette(palette: &[u8]) -> Vec<Color> {
+    palette.chunks_exact(3).map(|rgb| Color { r: rgb[0], g: rgb[1], b: rgb[2] }).collect()
+}
+
 /// Read the (next) frame of an `Animation`
 ///
 /// # Input
-/// A raw `gif::Frame` of the `Decoder` that to be parsed
+/// * `raw_frame`: A raw `gif::Frame` of the `Decoder` that to be parsed, with palette indices as buffer
+/// * `palette`: The RGB triples of the palette, the frame uses
+/// * `canvas`: The RGBA canvas the frame is drawn onto. It's disposed afterwards as the frame requests it
+/// * `indices`: The palette indices of the canvas, disposed the same way
+///
+/// # Output
+/// A `Frame` of our own kind
+fn read_frame(raw_frame: &gif::Frame, palette: &[u8], canvas: &mut [u8], indices: &mut [Option<u8>]) -> Frame {
+    let previous = canvas.to_vec();
+    let previous_indices = indices.to_vec();
+    let (left, top) = (raw_frame.left as usize, raw_frame.top as usize);
+    let (width, height) = (raw_frame.width as usize, raw_frame.height as usize);
+
+    //Draw all pixels of the frame, that are on screen and not transparent
+    for y in 0..height {
+        for x in 0..width {
+            let index = raw_frame.buffer[y * width + x];
+            if Some(index) != raw_frame.transparent && left + x < WIDTH && top + y < HEIGHT {
+                let rgb = palette.get(index as usize * 3..index as usize * 3 + 3).unwrap_or(&[0, 0, 0]);
+                let position = (top + y) * WIDTH + left + x;
+                canvas[position * 4..][..4].copy_from_slice(&[rgb[0], rgb[1], rgb[2], u8::MAX]);
+                indices[position] = Some(index);
+            }
+        }
+    }
+
+    let mut frame = frame_from_rgba(canvas, raw_frame.delay);
+    let mut grid = [[None; WIDTH]; HEIGHT];
+    for (position, index) in indices.iter().enumerate().take(WIDTH * HEIGHT) {
+        grid[position / WIDTH][position % WIDTH] = *index;
+    }
+    frame.indices = Some(grid);
+
+    //Prepare canvas for the next frame
+    match raw_frame.dispose {
+        gif::DisposalMethod::Background => {
+            for y in top..(top + height).min(HEIGHT) {
+                for x in left..(left + width).min(WIDTH) {
+                    canvas[(y * WIDTH + x) * 4..][..4].fill(0);
+                    indices[y * WIDTH + x] = None;
+                }
+            }
+        }
+        gif::DisposalMethod::Previous => {
+            canvas.copy_from_slice(&previous);
+            indices.copy_from_slice(&previous_indices);
+        }
+        gif::DisposalMethod::Any | gif::DisposalMethod::Keep => {}
+    }
+
+    info!("Read frame - Pixel count: {}, Delay: {} ms", frame.pixels.len() * frame.pixels[0].len(), frame.delay);
+    frame
+}
+
+/// Create a `Frame` from a buffer of RGBA values. Semi-transparent pixels get blended onto black,
+/// as that is what an unlit LED looks like
+///
+/// # Input
+/// * `buffer`: The RGBA values of all pixels, row by row
+/// * `delay`: The delay of the frame in multiples of 10 ms
 ///
 /// # Output
 /// A `Frame` of our own kind
-fn read_frame(raw_frame: &gif::Frame) -> Frame {
+fn frame_from_rgba(buffer: &[u8], delay: u16) -> Frame {
     //Init empty struct
     let mut frame: Frame = Frame {
         pixels: [[Pixel { r: 0, g: 0, b: 0, a: 0 }; WIDTH]; HEIGHT],
-        delay: raw_frame.delay,
+        delay,
+        indices: None,
+        grayscale: true,
     };
 
     //Read all pixels into 2D array
@@ -148,12 +410,13 @@ fn read_frame(raw_frame: &gif::Frame) -> Frame {
     //Meaning, given a frame has a total of 64 pixels (8x8), the buffer will have 256 entries
     //Thus were stepping through the buffer with the step size 4
     //todo: Yet, this could maybe be solved with an IntoIter/Iterator?
-    for i in (0..raw_frame.buffer.len()).step_by(4) {
+    for i in (0..buffer.len().min(WIDTH * HEIGHT * 4)).step_by(4) {
+        let a = buffer[i + 3];
         let pixel = Pixel {
-            r: raw_frame.buffer[i],
-            g: raw_frame.buffer[i + 1],
-            b: raw_frame.buffer[i + 2],
-            a: raw_frame.buffer[i + 3],
+            r: blend_on_black(buffer[i], a),
+            g: blend_on_black(buffer[i + 1], a),
+            b: blend_on_black(buffer[i + 2], a),
+            a,
         };
 
         //Convert 1D index into 2D index
@@ -165,10 +428,26 @@ fn read_frame(raw_frame: &gif::Frame) -> Frame {
         frame.pixels[x][y] = pixel;
     }
 
-    info!("Read frame - Pixel count: {}, Delay: {} ms", frame.pixels.len() * frame.pixels[0].len(), frame.delay);
+    frame.grayscale = frame_is_grayscale(&frame);
     frame
 }
 
+/// Blend a channel value with the given alpha onto black
+fn blend_on_black(channel: u8, alpha: u8) -> u8 {
+    ((channel as u16 * alpha as u16 + 127) / 255) as u8
+}
+
+/// Convert a frame delay in milliseconds to the GIF-like delay of a `Frame` (multiples of 10 ms)
+pub fn delay_from_millis(ms: u32) -> u16 {
+    ((ms + 5) / 10).min(u16::MAX as u32) as u16
+}
+
+/// Build an `Animation` from a list of `Frame`s and determine if it's grayscale
+fn animation_from_frames(frames: Vec<Frame>) -> Animation {
+    let grayscale = frames.iter().all(|frame| frame.grayscale);
+    Animation { frames, grayscale, palette: None }
+}
+
 /// Check if a given `Frame` is grayscale by checking all it's pixels
 ///
 /// # Input
@@ -211,12 +490,14 @@ pub fn pixel_is_black(pixel: &Pixel) -> bool {
 ///
 /// # Output
 /// If the `Pixel` is grayscale
-fn pixel_is_grayscale(pixel: &Pixel) -> bool {
+pub fn pixel_is_grayscale(pixel: &Pixel) -> bool {
     pixel.r == pixel.g && pixel.g == pixel.b
 }
 
 #[cfg(test)]
 mod tests {
+    use std::fs::File;
+
     use super::*;
 
     #[test]
@@ -239,30 +520,131 @@ mod tests {
         // Read a frame from a GIF file
         let file = File::open("gifs/gray.gif").unwrap();
         let mut decoder = gif::DecodeOptions::new().read_info(file).unwrap();
+        let palette = decoder.global_palette().unwrap_or_default().to_vec();
         let raw_frame = decoder.read_next_frame().unwrap().unwrap();
 
         // Test that the returned frame has the correct values
-        let frame = read_frame(&raw_frame);
+        let frame = read_frame(&raw_frame, &palette, &mut vec![0; WIDTH * HEIGHT * 4], &mut vec![None; WIDTH * HEIGHT]);
         assert_eq!(frame.delay, 0);
         assert_eq!(frame.pixels, [[Pixel { r: 0, g: 0, b: 0, a: 0 }; WIDTH]; HEIGHT]);
     }
 
+    #[test]
+    fn test_read_gif_indices() {
+        // Encode a GIF with a global palette, whose second frame only changes a single pixel
+        let mut data: Vec<u8> = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut data, WIDTH as u16, HEIGHT as u16, &[0, 0, 0, 255, 0, 0, 0, 0, 255]).unwrap();
+            encoder.write_frame(&gif::Frame { width: WIDTH as u16, height: HEIGHT as u16, buffer: vec![1; WIDTH * HEIGHT].into(), ..gif::Frame::default() }).unwrap();
+            encoder.write_frame(&gif::Frame { left: 1, width: 2, height: 1, transparent: Some(0), buffer: vec![2, 0].into(), ..gif::Frame::default() }).unwrap();
+        }
+
+        let anim = read_gif(&data).unwrap();
+        assert_eq!(anim.palette.unwrap()[..3], [Color { r: 0, g: 0, b: 0 }, Color { r: 255, g: 0, b: 0 }, Color { r: 0, g: 0, b: 255 }]);
+
+        let indices = anim.frames[1].indices.unwrap();
+        assert_eq!(indices[0][0..4], [Some(1), Some(2), Some(1), Some(1)]);
+        assert_eq!(anim.frames[1].pixels[0][1], Pixel { r: 0, g: 0, b: 255, a: 255 });
+        assert_eq!(anim.frames[1].pixels[0][2], Pixel { r: 255, g: 0, b: 0, a: 255 });
+    }
+
+    #[test]
+    fn test_read_gif_partial_frames() {
+        // A full red frame, followed by partial frames with every kind of disposal
+        let mut data: Vec<u8> = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut data, WIDTH as u16, HEIGHT as u16, &[0, 0, 0, 255, 0, 0, 0, 0, 255]).unwrap();
+            encoder.write_frame(&gif::Frame { width: WIDTH as u16, height: HEIGHT as u16, buffer: vec![1; WIDTH * HEIGHT].into(), ..gif::Frame::default() }).unwrap();
+            encoder.write_frame(&gif::Frame { left: 2, top: 1, width: 2, height: 2, dispose: gif::DisposalMethod::Background, buffer: vec![2; 4].into(), ..gif::Frame::default() }).unwrap();
+            encoder.write_frame(&gif::Frame { width: 1, height: 1, dispose: gif::DisposalMethod::Previous, buffer: vec![2].into(), ..gif::Frame::default() }).unwrap();
+            encoder.write_frame(&gif::Frame { left: 27, top: 7, width: 1, height: 1, buffer: vec![0].into(), ..gif::Frame::default() }).unwrap();
+        }
+
+        let red = Pixel { r: 255, g: 0, b: 0, a: 255 };
+        let blue = Pixel { r: 0, g: 0, b: 255, a: 255 };
+        let cleared = Pixel { r: 0, g: 0, b: 0, a: 0 };
+        let anim = read_gif(&data).unwrap();
+        assert_eq!(anim.frames.len(), 4);
+
+        // The partial frame is drawn onto the previous one
+        assert_eq!(anim.frames[1].pixels[1][2..5], [blue, blue, red]);
+        assert_eq!(anim.frames[1].pixels[0][2], red);
+
+        // Its area was cleared to the background afterwards
+        assert_eq!(anim.frames[2].pixels[0][0], blue);
+        assert_eq!(anim.frames[2].pixels[1][2..5], [cleared, cleared, red]);
+        assert_eq!(anim.frames[2].indices.unwrap()[1][2], None);
+
+        // The canvas before the third frame was restored afterwards
+        assert_eq!(anim.frames[3].pixels[0][0], red);
+        assert_eq!(anim.frames[3].pixels[2][3], cleared);
+        assert_eq!(anim.frames[3].pixels[7][27], Pixel { r: 0, g: 0, b: 0, a: 255 });
+        assert_eq!(anim.frames[3].pixels[7][26], red);
+    }
+
+    #[test]
+    fn test_read_gif_dimensions() {
+        let mut data: Vec<u8> = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut data, 4, 4, &[0, 0, 0]).unwrap();
+            encoder.write_frame(&gif::Frame { width: 4, height: 4, buffer: vec![0; 16].into(), ..gif::Frame::default() }).unwrap();
+        }
+
+        assert!(matches!(read_gif(&data), Err(GifError::Dimensions(4, 4))));
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(AnimationFormat::detect(b"GIF89a..."), Some(AnimationFormat::Gif));
+        assert_eq!(AnimationFormat::detect(b"GIF87a..."), Some(AnimationFormat::Gif));
+        assert_eq!(AnimationFormat::detect(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0]), Some(AnimationFormat::Png));
+        assert_eq!(AnimationFormat::detect(b"RIFF\x10\0\0\0WEBPVP8L"), Some(AnimationFormat::WebP));
+        assert_eq!(AnimationFormat::detect(b"RIFF\x10\0\0\0WAVEfmt "), None);
+        assert_eq!(AnimationFormat::detect(&[0, 0, 0, 0, 0xE0, 0xA5, 1, 0]), Some(AnimationFormat::Aseprite));
+        assert_eq!(AnimationFormat::detect(b"tasbot-eyes-text\npalette"), Some(AnimationFormat::Text));
+        assert_eq!(AnimationFormat::detect(b"  {\"data_type\": \"sprite_sheet\"}"), Some(AnimationFormat::SpriteSheet));
+        assert_eq!(AnimationFormat::detect(b"{\"data_type\": \"palette\", \"colors\": []}"), None);
+        assert_eq!(AnimationFormat::detect(b"{\"tags\": [\"happy\"], \"weight\": 2.0}"), None);
+        assert_eq!(AnimationFormat::detect(b"{\"data_type\": \"sprite_"), None);
+        assert_eq!(AnimationFormat::detect(&[0, 1, 2, 3, 4]), None);
+    }
+
+    #[test]
+    fn test_split_tag() {
+        let (path, tag) = split_tag(Path::new("gifs/others/eyes.aseprite#blink"));
+        assert_eq!(path, PathBuf::from("gifs/others/eyes.aseprite"));
+        assert_eq!(tag, Some("blink".to_owned()));
+
+        let (path, tag) = split_tag(Path::new("gifs/others/eyes.aseprite"));
+        assert_eq!(path, PathBuf::from("gifs/others/eyes.aseprite"));
+        assert_eq!(tag, None);
+    }
+
+    #[test]
+    fn test_frame_from_rgba() {
+        let mut buffer = vec![0u8; WIDTH * HEIGHT * 4];
+        buffer[0..4].copy_from_slice(&[255, 255, 255, 255]);
+        buffer[4..8].copy_from_slice(&[200, 100, 50, 0]);
+        buffer[(WIDTH * 4)..(WIDTH * 4 + 4)].copy_from_slice(&[255, 128, 0, 128]);
+
+        let frame = frame_from_rgba(&buffer, 7);
+        assert_eq!(frame.delay, 7);
+        assert_eq!(frame.pixels[0][0], Pixel { r: 255, g: 255, b: 255, a: 255 });
+        assert_eq!(frame.pixels[0][1], Pixel { r: 0, g: 0, b: 0, a: 0 });
+        assert_eq!(frame.pixels[1][0], Pixel { r: 128, g: 64, b: 0, a: 128 });
+    }
 
     #[test]
     fn test_frame_is_grayscale() {
         // Test a grayscale frame
-        let frame = Frame {
-            pixels: [[Pixel { r: 128, g: 128, b: 128, a: 255 }; WIDTH]; HEIGHT],
-            delay: 100,
-        };
+        let frame = Frame::from_pixels([[Pixel { r: 128, g: 128, b: 128, a: 255 }; WIDTH]; HEIGHT], 100);
         assert_eq!(frame_is_grayscale(&frame), true);
+        assert!(frame.grayscale);
 
         // Test a colorful frame
-        let frame = Frame {
-            pixels: [[Pixel { r: 128, g: 128, b: 64, a: 255 }; WIDTH]; HEIGHT],
-            delay: 100,
-        };
+        let frame = Frame::from_pixels([[Pixel { r: 128, g: 128, b: 64, a: 255 }; WIDTH]; HEIGHT], 100);
         assert_eq!(frame_is_grayscale(&frame), false);
+        assert!(!frame.grayscale);
     }
 
     #[test]
@@ -0,0 +1,193 @@
+use png::{BlendOp, ColorType, DisposeOp, Transformations};
+
+use crate::gif::{Animation, animation_from_frames, delay_from_millis, Frame, frame_from_rgba, GifError, HEIGHT, WIDTH};
+
+/// Read an animation from the bytes of a PNG file. Animated PNGs get all their frames composed
+/// onto a canvas, still PNGs result in an `Animation` with a single frame
+///
+/// # Input
+/// The content of the PNG file
+///
+/// # Output
+/// A `Result<Animation, GifError>`, that fails when:
+/// * The PNG can't be decoded
+/// * The PNG is not as big as TASBots display
+pub fn read_apng(data: &[u8]) -> Result<Animation, GifError> {
+    //Setup decoder, so every color type ends up as 8 bit per sample
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+
+    let (width, height) = reader.info().size();
+    if width as usize != WIDTH || height as usize != HEIGHT {
+        return Err(GifError::Dimensions(width, height));
+    }
+
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame_count = match reader.info().animation_control {
+        None => 1,
+        Some(control) => {
+            //Without a frame control before the image data, the default image isn't part of the animation
+            if reader.info().frame_control.is_none() {
+                reader.next_frame(&mut buffer)?;
+            }
+            control.num_frames
+        }
+    };
+
+    let mut canvas = vec![0u8; WIDTH * HEIGHT * 4];
+    let mut frames: Vec<Frame> = Vec::new();
+
+    for i in 0..frame_count {
+        let output = reader.next_frame(&mut buffer)?;
+        let subframe = to_rgba(&buffer[..output.buffer_size()], output.color_type);
+
+        //Still PNGs have no frame control, they cover the whole image
+        let control = reader.info().frame_control.unwrap_or_default();
+        let (x_offset, y_offset) = (control.x_offset as usize, control.y_offset as usize);
+        let (sub_width, sub_height) = (output.width as usize, output.height as usize);
+        if x_offset + sub_width > WIDTH || y_offset + sub_height > HEIGHT {
+            return Err(GifError::Dimensions((x_offset + sub_width) as u32, (y_offset + sub_height) as u32));
+        }
+
+        let previous = canvas.clone();
+
+        //Draw subframe onto canvas
+        for y in 0..sub_height {
+            for x in 0..sub_width {
+                let src = &subframe[(y * sub_width + x) * 4..][..4];
+                let dst = &mut canvas[((y + y_offset) * WIDTH + x + x_offset) * 4..][..4];
+
+                match control.blend_op {
+                    BlendOp::Source => dst.copy_from_slice(src),
+                    BlendOp::Over => blend_over(src, dst),
+                }
+            }
+        }
+
+        let delay = if control.delay_den == 0 {
+            delay_from_millis(control.delay_num as u32 * 10)
+        } else {
+            delay_from_millis(control.delay_num as u32 * 1000 / control.delay_den as u32)
+        };
+        frames.push(frame_from_rgba(&canvas, delay));
+
+        //Dispose subframe area for the next frame. The first frame can't be reverted
+        match control.dispose_op {
+            DisposeOp::None => {}
+            DisposeOp::Previous if i > 0 => canvas = previous,
+            DisposeOp::Background | DisposeOp::Previous => {
+                for y in y_offset..y_offset + sub_height {
+                    canvas[(y * WIDTH + x_offset) * 4..(y * WIDTH + x_offset + sub_width) * 4].fill(0);
+                }
+            }
+        }
+    }
+
+    Ok(animation_from_frames(frames))
+}
+
+/// Convert the (8 bit per sample) output of the PNG decoder to RGBA values
+///
+/// # Input
+/// * `data`: The decoded samples
+/// * `color_type`: The color type of the samples
+///
+/// # Output
+/// The samples as RGBA values
+pub fn to_rgba(data: &[u8], color_type: ColorType) -> Vec<u8> {
+    match color_type {
+        ColorType::Rgba => data.to_vec(),
+        ColorType::Rgb => data.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], u8::MAX]).collect(),
+        ColorType::GrayscaleAlpha => data.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+        //Indexed images are already expanded by the decoder
+        ColorType::Grayscale | ColorType::Indexed => data.iter().flat_map(|v| [*v, *v, *v, u8::MAX]).collect(),
+    }
+}
+
+/// Blend a RGBA source pixel over a RGBA destination pixel
+///
+/// # Input
+/// * `src`: The pixel that is drawn
+/// * `dst`: The pixel that is drawn onto and that receives the result
+fn blend_over(src: &[u8], dst: &mut [u8]) {
+    let src_a = src[3] as u32;
+    let dst_a = dst[3] as u32 * (255 - src_a) / 255;
+    let out_a = src_a + dst_a;
+
+    if out_a == 0 {
+        dst.fill(0);
+        return;
+    }
+
+    for c in 0..3 {
+        dst[c] = ((src[c] as u32 * src_a + dst[c] as u32 * dst_a) / out_a) as u8;
+    }
+    dst[3] = out_a as u8;
+}
+
+#[cfg(test)]
+mod tests {
+    use png::{BitDepth, Encoder};
+
+    use super::*;
+
+    /// Encode the given RGBA frames as APNG with 50 ms per frame
+    fn encode_apng(frames: &[Vec<u8>]) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut data, WIDTH as u32, HEIGHT as u32);
+            encoder.set_color(ColorType::Rgba);
+            encoder.set_depth(BitDepth::Eight);
+            encoder.set_animated(frames.len() as u32, 0).unwrap();
+            encoder.set_frame_delay(1, 20).unwrap();
+
+            let mut writer = encoder.write_header().unwrap();
+            for frame in frames {
+                writer.write_image_data(frame).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+        data
+    }
+
+    #[test]
+    fn test_read_apng() {
+        let mut first = vec![0u8; WIDTH * HEIGHT * 4];
+        first[0..4].copy_from_slice(&[255, 255, 255, 255]);
+        let mut second = vec![0u8; WIDTH * HEIGHT * 4];
+        second[4..8].copy_from_slice(&[255, 0, 0, 255]);
+
+        let anim = read_apng(&encode_apng(&[first, second])).unwrap();
+        assert_eq!(anim.frames.len(), 2);
+        assert!(!anim.grayscale);
+        assert_eq!(anim.frames[0].delay, 5);
+        assert_eq!(anim.frames[0].pixels[0][0].r, 255);
+        assert_eq!(anim.frames[0].pixels[0][1].r, 0);
+        assert_eq!(anim.frames[1].pixels[0][1].r, 255);
+    }
+
+    #[test]
+    fn test_read_apng_wrong_size() {
+        let mut data: Vec<u8> = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut data, 4, 4);
+            encoder.set_color(ColorType::Grayscale);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[0u8; 16]).unwrap();
+        }
+
+        let result = read_apng(&data);
+        assert!(matches!(result, Err(GifError::Dimensions(4, 4))));
+    }
+
+    #[test]
+    fn test_blend_over() {
+        let mut dst = [0, 0, 255, 255];
+        blend_over(&[255, 0, 0, 0], &mut dst);
+        assert_eq!(dst, [0, 0, 255, 255]);
+
+        blend_over(&[255, 0, 0, 255], &mut dst);
+        assert_eq!(dst, [255, 0, 0, 255]);
+    }
+}
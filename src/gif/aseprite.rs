@@ -0,0 +1,571 @@
+use std::io::Read;
+
+use flate2::read::ZlibDecoder;
+use log::{info, warn};
+
+use crate::gif::{Animation, animation_from_frames, delay_from_millis, Frame, frame_from_rgba, GifError, HEIGHT, WIDTH};
+
+/// The magic number of the file header
+const HEADER_MAGIC: u16 = 0xA5E0;
+
+/// The magic number of every frame header
+const FRAME_MAGIC: u16 = 0xF1FA;
+
+/// The size of the file header in bytes
+const HEADER_SIZE: usize = 128;
+
+/// Chunk containing the palette in the old format
+const CHUNK_OLD_PALETTE: u16 = 0x0004;
+
+/// Chunk describing a layer
+const CHUNK_LAYER: u16 = 0x2004;
+
+/// Chunk containing the image of a layer in a frame
+const CHUNK_CEL: u16 = 0x2005;
+
+/// Chunk containing all tags
+const CHUNK_TAGS: u16 = 0x2018;
+
+/// Chunk containing the palette in the new format
+const CHUNK_PALETTE: u16 = 0x2019;
+
+/// Layer flag, if the layer is visible
+const LAYER_FLAG_VISIBLE: u16 = 1;
+
+/// Header flag, if the opacity of the layers is valid
+const HEADER_FLAG_LAYER_OPACITY: u32 = 1;
+
+/// Layer type of groups
+const LAYER_TYPE_GROUP: u16 = 1;
+
+/// A layer of the file
+struct Layer {
+    /// If the layer and all its parent groups are visible
+    visible: bool,
+
+    /// Opacity of the layer
+    opacity: u8,
+}
+
+/// The image of a layer in a frame. The pixels are kept in the color depth of the file
+#[derive(Clone)]
+struct Cel {
+    layer: usize,
+    x: i16,
+    y: i16,
+    opacity: u8,
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+/// A tag, which is a named range of frames
+struct Tag {
+    from: usize,
+    to: usize,
+    direction: u8,
+    name: String,
+}
+
+/// Read an animation from the bytes of an Aseprite file (`.aseprite` or `.ase`). All visible
+/// layers get flattened, the frame durations are used as delays
+///
+/// # Input
+/// * `data`: The content of the Aseprite file
+/// * `tag`: The optional name of a tag, so only its frames are read
+///
+/// # Output
+/// A `Result<Animation, GifError>`, that fails when:
+/// * The file is malformed
+/// * The canvas is not as big as TASBots display
+/// * The tag cannot be found
+pub fn read_aseprite(data: &[u8], tag: Option<&str>) -> Result<Animation, GifError> {
+    let mut reader = ByteReader::new(data);
+
+    //Header
+    reader.skip(4)?;
+    if reader.u16()? != HEADER_MAGIC {
+        return Err(GifError::Aseprite("Invalid file header".to_owned()));
+    }
+    let frame_count = reader.u16()? as usize;
+    let width = reader.u16()?;
+    let height = reader.u16()?;
+    let depth = reader.u16()?;
+    let flags = reader.u32()?;
+    reader.skip(10)?;
+    let transparent_index = reader.u8()?;
+
+    if width as usize != WIDTH || height as usize != HEIGHT {
+        return Err(GifError::Dimensions(width as u32, height as u32));
+    }
+    if depth != 32 && depth != 16 && depth != 8 {
+        return Err(GifError::Aseprite(format!("Unsupported color depth of {} bit", depth)));
+    }
+    reader.seek(HEADER_SIZE)?;
+
+    let mut layers: Vec<Layer> = Vec::new();
+    let mut group_visibility: Vec<bool> = Vec::new();
+    let mut palette: Vec<[u8; 4]> = Vec::new();
+    let mut has_new_palette = false;
+    let mut tags: Vec<Tag> = Vec::new();
+    let mut durations: Vec<u16> = Vec::new();
+    let mut cels: Vec<Vec<Cel>> = Vec::new();
+
+    //Frames
+    for _ in 0..frame_count {
+        let frame_start = reader.pos;
+        let frame_size = reader.u32()? as usize;
+        if reader.u16()? != FRAME_MAGIC {
+            return Err(GifError::Aseprite("Invalid frame header".to_owned()));
+        }
+        let old_chunk_count = reader.u16()? as usize;
+        durations.push(reader.u16()?);
+        reader.skip(2)?;
+        let chunk_count = match reader.u32()? as usize {
+            0 => old_chunk_count,
+            count => count,
+        };
+
+        let mut frame_cels: Vec<Cel> = Vec::new();
+        for _ in 0..chunk_count {
+            let chunk_start = reader.pos;
+            let chunk_size = reader.u32()? as usize;
+            let chunk_type = reader.u16()?;
+
+            match chunk_type {
+                CHUNK_LAYER => {
+                    let layer_flags = reader.u16()?;
+                    let layer_type = reader.u16()?;
+                    let level = reader.u16()? as usize;
+                    reader.skip(6)?;
+                    let opacity = reader.u8()?;
+
+                    //A layer is only visible, if all the groups its nested in are visible as well
+                    let parent_visible = level == 0 || group_visibility.get(level - 1).copied().unwrap_or(true);
+                    let visible = parent_visible && layer_flags & LAYER_FLAG_VISIBLE != 0;
+                    group_visibility.truncate(level);
+                    group_visibility.push(visible);
+
+                    layers.push(Layer {
+                        visible: visible && layer_type != LAYER_TYPE_GROUP,
+                        opacity: if flags & HEADER_FLAG_LAYER_OPACITY != 0 { opacity } else { u8::MAX },
+                    });
+                }
+                CHUNK_CEL => {
+                    let layer = reader.u16()? as usize;
+                    let x = reader.i16()?;
+                    let y = reader.i16()?;
+                    let opacity = reader.u8()?;
+                    let cel_type = reader.u16()?;
+                    reader.skip(7)?;
+
+                    match cel_type {
+                        //Raw or compressed image
+                        0 | 2 => {
+                            let width = reader.u16()? as usize;
+                            let height = reader.u16()? as usize;
+                            let size = width * height * (depth as usize / 8);
+                            let raw = reader.bytes((chunk_start + chunk_size).saturating_sub(reader.pos))?;
+
+                            let pixels = if cel_type == 0 {
+                                raw.get(..size).map(|p| p.to_vec())
+                            } else {
+                                let mut pixels = Vec::with_capacity(size);
+                                ZlibDecoder::new(raw).read_to_end(&mut pixels)?;
+                                Some(pixels).filter(|p| p.len() >= size)
+                            };
+
+                            match pixels {
+                                None => return Err(GifError::Aseprite("Cel is too small".to_owned())),
+                                Some(pixels) => frame_cels.push(Cel { layer, x, y, opacity, width, height, pixels }),
+                            }
+                        }
+
+                        //Linked cel, which reuses the cel of an earlier frame
+                        1 => {
+                            let linked_frame = reader.u16()? as usize;
+                            let linked = cels.get(linked_frame)
+                                .and_then(|c| c.iter().find(|cel| cel.layer == layer))
+                                .cloned();
+
+                            match linked {
+                                None => warn!("Can't find linked cel of frame {}", linked_frame),
+                                Some(cel) => frame_cels.push(Cel { x, y, opacity, ..cel }),
+                            }
+                        }
+
+                        _ => warn!("Skipping unsupported cel type {}", cel_type),
+                    }
+                }
+                CHUNK_PALETTE => {
+                    has_new_palette = true;
+                    let size = reader.u32()? as usize;
+                    let first = reader.u32()? as usize;
+                    let last = reader.u32()? as usize;
+                    reader.skip(8)?;
+
+                    palette.resize(size.max(palette.len()), [0, 0, 0, u8::MAX]);
+                    for index in first..=last {
+                        let entry_flags = reader.u16()?;
+                        let color = [reader.u8()?, reader.u8()?, reader.u8()?, reader.u8()?];
+                        if entry_flags & 1 != 0 {
+                            reader.string()?;
+                        }
+                        if index < palette.len() {
+                            palette[index] = color;
+                        }
+                    }
+                }
+                CHUNK_OLD_PALETTE if !has_new_palette => {
+                    let packets = reader.u16()?;
+                    let mut index = 0;
+                    for _ in 0..packets {
+                        index += reader.u8()? as usize;
+                        let count = match reader.u8()? { 0 => 256, count => count as usize };
+                        palette.resize((index + count).max(palette.len()), [0, 0, 0, u8::MAX]);
+                        for _ in 0..count {
+                            palette[index] = [reader.u8()?, reader.u8()?, reader.u8()?, u8::MAX];
+                            index += 1;
+                        }
+                    }
+                }
+                CHUNK_TAGS => {
+                    let count = reader.u16()?;
+                    reader.skip(8)?;
+                    for _ in 0..count {
+                        let from = reader.u16()? as usize;
+                        let to = reader.u16()? as usize;
+                        let direction = reader.u8()?;
+                        reader.skip(12)?;
+                        let name = reader.string()?;
+                        tags.push(Tag { from, to, direction, name });
+                    }
+                }
+                _ => {}
+            }
+
+            reader.seek(chunk_start + chunk_size)?;
+        }
+
+        cels.push(frame_cels);
+        reader.seek(frame_start + frame_size)?;
+    }
+
+    //Flatten all visible layers of every frame
+    let mut frames: Vec<Frame> = Vec::new();
+    for (frame_cels, duration) in cels.iter_mut().zip(durations) {
+        frame_cels.sort_by_key(|cel| cel.layer);
+
+        let mut canvas = vec![0u8; WIDTH * HEIGHT * 4];
+        for cel in frame_cels.iter() {
+            match layers.get(cel.layer) {
+                Some(layer) if layer.visible => {
+                    let opacity = cel.opacity as u32 * layer.opacity as u32 / 255;
+                    draw_cel(&mut canvas, cel, depth, &palette, transparent_index, opacity);
+                }
+                _ => {}
+            }
+        }
+
+        frames.push(frame_from_rgba(&canvas, delay_from_millis(duration as u32)));
+    }
+
+    //Only keep the frames of the wanted tag
+    if let Some(tag_name) = tag {
+        match tags.iter().find(|t| t.name == tag_name) {
+            None => return Err(GifError::Aseprite(format!("Can't find tag \"{}\"", tag_name))),
+            Some(tag) => {
+                info!("Using frames {} to {} of tag \"{}\"", tag.from, tag.to, tag.name);
+                frames = apply_tag(frames, tag);
+            }
+        }
+    }
+
+    Ok(animation_from_frames(frames))
+}
+
+/// Draw a cel onto the canvas
+///
+/// # Input
+/// * `canvas`: The RGBA canvas the cel is drawn onto
+/// * `cel`: The cel that is to draw
+/// * `depth`: The color depth of the file
+/// * `palette`: The palette of the file, that is used by indexed files
+/// * `transparent_index`: The palette index, that is transparent in indexed files
+/// * `opacity`: The combined opacity of the cel and its layer
+fn draw_cel(canvas: &mut [u8], cel: &Cel, depth: u16, palette: &[[u8; 4]], transparent_index: u8, opacity: u32) {
+    for y in 0..cel.height {
+        for x in 0..cel.width {
+            let canvas_x = cel.x as isize + x as isize;
+            let canvas_y = cel.y as isize + y as isize;
+            if canvas_x < 0 || canvas_y < 0 || canvas_x >= WIDTH as isize || canvas_y >= HEIGHT as isize {
+                continue;
+            }
+
+            let i = y * cel.width + x;
+            let src: [u8; 4] = match depth {
+                32 => [cel.pixels[i * 4], cel.pixels[i * 4 + 1], cel.pixels[i * 4 + 2], cel.pixels[i * 4 + 3]],
+                16 => [cel.pixels[i * 2], cel.pixels[i * 2], cel.pixels[i * 2], cel.pixels[i * 2 + 1]],
+                _ => {
+                    let index = cel.pixels[i];
+                    if index == transparent_index {
+                        [0, 0, 0, 0]
+                    } else {
+                        palette.get(index as usize).copied().unwrap_or([0, 0, 0, 0])
+                    }
+                }
+            };
+
+            //Normal blending with the opacity of cel and layer
+            let src_a = src[3] as u32 * opacity / 255;
+            let dst = &mut canvas[(canvas_y as usize * WIDTH + canvas_x as usize) * 4..][..4];
+            let dst_a = dst[3] as u32 * (255 - src_a) / 255;
+            let out_a = src_a + dst_a;
+            if out_a == 0 {
+                continue;
+            }
+
+            for c in 0..3 {
+                dst[c] = ((src[c] as u32 * src_a + dst[c] as u32 * dst_a) / out_a) as u8;
+            }
+            dst[3] = out_a as u8;
+        }
+    }
+}
+
+/// Cut the frames down to the range of a tag and order them according to its loop direction
+///
+/// # Input
+/// * `frames`: All frames of the file
+/// * `tag`: The tag, which frames are to keep
+///
+/// # Output
+/// The frames of the tag
+fn apply_tag(frames: Vec<Frame>, tag: &Tag) -> Vec<Frame> {
+    let mut tagged: Vec<Frame> = frames.into_iter()
+        .skip(tag.from)
+        .take(tag.to.saturating_sub(tag.from) + 1)
+        .collect();
+
+    match tag.direction {
+        //Reverse
+        1 => tagged.reverse(),
+
+        //Ping-pong (and ping-pong reverse), without repeating the first and last frame
+        2 | 3 => {
+            if tag.direction == 3 {
+                tagged.reverse();
+            }
+            let back: Vec<Frame> = tagged.iter()
+                .rev()
+                .skip(1)
+                .take(tagged.len().saturating_sub(2))
+                .cloned()
+                .collect();
+            tagged.extend(back);
+        }
+
+        //Forward
+        _ => {}
+    }
+
+    tagged
+}
+
+/// Reads little endian values from a byte slice, as used by Aseprite files
+struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        ByteReader { data, pos: 0 }
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], GifError> {
+        match self.data.get(self.pos..self.pos + count) {
+            None => Err(GifError::Aseprite("Unexpected end of file".to_owned())),
+            Some(bytes) => {
+                self.pos += count;
+                Ok(bytes)
+            }
+        }
+    }
+
+    fn seek(&mut self, pos: usize) -> Result<(), GifError> {
+        if pos > self.data.len() {
+            return Err(GifError::Aseprite("Unexpected end of file".to_owned()));
+        }
+        self.pos = pos;
+        Ok(())
+    }
+
+    fn skip(&mut self, count: usize) -> Result<(), GifError> {
+        self.bytes(count).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8, GifError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, GifError> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn i16(&mut self) -> Result<i16, GifError> {
+        let b = self.bytes(2)?;
+        Ok(i16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, GifError> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn string(&mut self) -> Result<String, GifError> {
+        let length = self.u16()? as usize;
+        Ok(String::from_utf8_lossy(self.bytes(length)?).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::Compression;
+    use flate2::write::ZlibEncoder;
+
+    use super::*;
+
+    /// Wrap chunk data with its header
+    fn chunk(chunk_type: u16, data: &[u8]) -> Vec<u8> {
+        let mut result = ((data.len() + 6) as u32).to_le_bytes().to_vec();
+        result.extend(chunk_type.to_le_bytes());
+        result.extend(data);
+        result
+    }
+
+    /// Wrap chunks with a frame header
+    fn frame(duration: u16, chunks: &[Vec<u8>]) -> Vec<u8> {
+        let body: Vec<u8> = chunks.concat();
+        let mut result = ((body.len() + 16) as u32).to_le_bytes().to_vec();
+        result.extend(FRAME_MAGIC.to_le_bytes());
+        result.extend((chunks.len() as u16).to_le_bytes());
+        result.extend(duration.to_le_bytes());
+        result.extend([0, 0]);
+        result.extend((chunks.len() as u32).to_le_bytes());
+        result.extend(body);
+        result
+    }
+
+    /// Build an RGBA Aseprite file out of the given frames
+    fn file(frames: &[Vec<u8>]) -> Vec<u8> {
+        let body: Vec<u8> = frames.concat();
+        let mut result = ((body.len() + HEADER_SIZE) as u32).to_le_bytes().to_vec();
+        result.extend(HEADER_MAGIC.to_le_bytes());
+        result.extend((frames.len() as u16).to_le_bytes());
+        result.extend((WIDTH as u16).to_le_bytes());
+        result.extend((HEIGHT as u16).to_le_bytes());
+        result.extend(32u16.to_le_bytes());
+        result.extend(HEADER_FLAG_LAYER_OPACITY.to_le_bytes());
+        result.resize(HEADER_SIZE, 0);
+        result.extend(body);
+        result
+    }
+
+    fn layer(name: &str, visible: bool) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend((if visible { LAYER_FLAG_VISIBLE } else { 0 }).to_le_bytes());
+        data.extend([0u8; 10]);
+        data.push(255);
+        data.extend([0u8; 3]);
+        data.extend((name.len() as u16).to_le_bytes());
+        data.extend(name.as_bytes());
+        chunk(CHUNK_LAYER, &data)
+    }
+
+    /// A cel covering a single pixel at the given position
+    fn cel(layer: u16, x: i16, color: [u8; 4], compressed: bool) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend(layer.to_le_bytes());
+        data.extend(x.to_le_bytes());
+        data.extend(0i16.to_le_bytes());
+        data.push(255);
+        data.extend((if compressed { 2u16 } else { 0u16 }).to_le_bytes());
+        data.extend([0u8; 7]);
+        data.extend(1u16.to_le_bytes());
+        data.extend(1u16.to_le_bytes());
+        if compressed {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&color).unwrap();
+            data.extend(encoder.finish().unwrap());
+        } else {
+            data.extend(color);
+        }
+        chunk(CHUNK_CEL, &data)
+    }
+
+    fn tags(name: &str, from: u16, to: u16, direction: u8) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend(1u16.to_le_bytes());
+        data.extend([0u8; 8]);
+        data.extend(from.to_le_bytes());
+        data.extend(to.to_le_bytes());
+        data.push(direction);
+        data.extend([0u8; 12]);
+        data.extend((name.len() as u16).to_le_bytes());
+        data.extend(name.as_bytes());
+        chunk(CHUNK_TAGS, &data)
+    }
+
+    fn test_file() -> Vec<u8> {
+        file(&[
+            frame(100, &[
+                layer("visible", true),
+                layer("hidden", false),
+                tags("blink", 1, 2, 0),
+                cel(0, 0, [255, 255, 255, 255], false),
+                cel(1, 1, [255, 0, 0, 255], false),
+            ]),
+            frame(50, &[cel(0, 1, [128, 128, 128, 255], true)]),
+            frame(30, &[cel(0, 2, [64, 64, 64, 255], false)]),
+        ])
+    }
+
+    #[test]
+    fn test_read_aseprite() {
+        let anim = read_aseprite(&test_file(), None).unwrap();
+        assert_eq!(anim.frames.len(), 3);
+        assert!(anim.grayscale);
+        assert_eq!(anim.frames[0].delay, 10);
+        assert_eq!(anim.frames[1].delay, 5);
+        assert_eq!(anim.frames[0].pixels[0][0].r, 255);
+        assert_eq!(anim.frames[0].pixels[0][1].r, 0);
+        assert_eq!(anim.frames[1].pixels[0][1].r, 128);
+        assert_eq!(anim.frames[2].pixels[0][2].r, 64);
+    }
+
+    #[test]
+    fn test_read_aseprite_tag() {
+        let anim = read_aseprite(&test_file(), Some("blink")).unwrap();
+        assert_eq!(anim.frames.len(), 2);
+        assert_eq!(anim.frames[0].delay, 5);
+        assert_eq!(anim.frames[1].delay, 3);
+
+        let result = read_aseprite(&test_file(), Some("missing"));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_apply_tag_ping_pong() {
+        let frames: Vec<Frame> = (0..4)
+            .map(|delay| frame_from_rgba(&[], delay))
+            .collect();
+        let tag = Tag { from: 0, to: 3, direction: 2, name: String::new() };
+
+        let delays: Vec<u16> = apply_tag(frames, &tag).iter().map(|f| f.delay).collect();
+        assert_eq!(delays, vec![0, 1, 2, 3, 2, 1]);
+    }
+}
@@ -0,0 +1,177 @@
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::color::Color;
+use crate::gif::{Animation, Frame, frame_is_grayscale, HEIGHT, Pixel, WIDTH};
+
+/// The shortest time (in 10 ms) frames are split into, while their colors cycle
+const MIN_STEP: u16 = 2;
+
+/// The most steps a single frame is split into. Longer frames get longer steps, so a hold of a few seconds
+/// doesn't turn into hundreds of frames
+pub const MAX_STEPS: u16 = 25;
+
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
+/// A range of the palette, whose colors rotate over time, e.g. `{"range": [16, 31], "speed": 8}`
+pub struct ColorCycle {
+    /// The first and the last palette index of the range, both included
+    pub range: (u8, u8),
+
+    /// How many indices per second the colors move up. Negative values move them down
+    pub speed: f32,
+}
+
+/// Rotate the palette colors of an animation over time, the classic color cycling. As renderers show frames
+/// as they are, every frame is split into steps, that are short enough for the fastest cycle. A frame is split into
+/// at most `MAX_STEPS` steps, so the colors of long frames move in bigger jumps
+///
+/// # Input
+/// * `anim`: The `Animation` that is to cycle. It needs a palette, like most GIFs have
+/// * `cycles`: The ranges of the palette, that are to rotate
+pub fn apply_color_cycles(anim: &mut Animation, cycles: &[ColorCycle]) {
+    if cycles.is_empty() {
+        return;
+    }
+
+    let Some(palette) = anim.palette.clone() else {
+        warn!("Can't cycle colors of an animation without palette");
+        return;
+    };
+
+    let fastest = cycles.iter().map(|cycle| cycle.speed.abs()).fold(0.0, f32::max);
+    if fastest == 0.0 {
+        return;
+    }
+    let step = ((100.0 / fastest).round() as u16).max(MIN_STEP);
+
+    //The time keeps running over all frames, so the colors move smoothly from one frame to the next
+    let mut time: u32 = 0;
+    let mut frames: Vec<Frame> = Vec::new();
+    for frame in anim.frames.drain(..) {
+        let Some(indices) = frame.indices else {
+            time += frame.delay as u32;
+            frames.push(frame);
+            continue;
+        };
+
+        let step = step.max(frame.delay.div_ceil(MAX_STEPS));
+        let mut remaining = frame.delay;
+        loop {
+            let delay = remaining.min(step);
+            let colors = cycle_palette(&palette, cycles, time as f32 / 100.0);
+            frames.push(recolor(&frame, &indices, &colors, delay));
+
+            time += delay as u32;
+            remaining -= delay;
+            if remaining == 0 {
+                break;
+            }
+        }
+    }
+
+    anim.grayscale = frames.iter().all(|frame| frame.grayscale);
+    anim.frames = frames;
+}
+
+/// Get the palette at a point in time
+///
+/// # Input
+/// * `palette`: The original palette
+/// * `cycles`: The ranges of the palette, that rotate
+/// * `seconds`: The time since the start of the animation
+///
+/// # Output
+/// The palette with all ranges rotated
+fn cycle_palette(palette: &[Color], cycles: &[ColorCycle], seconds: f32) -> Vec<Color> {
+    let mut colors = palette.to_vec();
+
+    for cycle in cycles {
+        let (start, end) = (cycle.range.0 as usize, (cycle.range.1 as usize).min(palette.len().saturating_sub(1)));
+        if start >= end {
+            continue;
+        }
+
+        let length = end - start + 1;
+        let offset = ((seconds * cycle.speed).floor() as i64).rem_euclid(length as i64) as usize;
+        colors[start..=end].rotate_right(offset);
+    }
+
+    colors
+}
+
+/// Create a copy of a frame, whose pixels get the colors of their palette indices
+///
+/// # Input
+/// * `frame`: The original `Frame`
+/// * `indices`: The palette indices of the frame
+/// * `colors`: The palette the indices point into
+/// * `delay`: The delay of the copy
+fn recolor(frame: &Frame, indices: &[[Option<u8>; WIDTH]; HEIGHT], colors: &[Color], delay: u16) -> Frame {
+    let mut recolored = Frame { delay, ..frame.clone() };
+
+    for (row, index_row) in recolored.pixels.iter_mut().zip(indices) {
+        for (pixel, index) in row.iter_mut().zip(index_row) {
+            if let Some(color) = index.and_then(|index| colors.get(index as usize)) {
+                *pixel = Pixel { r: color.r, g: color.g, b: color.b, a: u8::MAX };
+            }
+        }
+    }
+
+    recolored.grayscale = frame_is_grayscale(&recolored);
+    recolored
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::{BLUE, GREEN, RED, WHITE};
+    use crate::gif::fixtures::indexed_animation;
+
+    use super::*;
+
+    fn first_pixels(anim: &Animation) -> Vec<(Color, u16)> {
+        anim.frames.iter().map(|frame| {
+            let pixel = frame.pixels[0][0];
+            (Color { r: pixel.r, g: pixel.g, b: pixel.b }, frame.delay)
+        }).collect()
+    }
+
+    #[test]
+    fn test_cycle_palette() {
+        let palette = vec![WHITE, RED, GREEN, BLUE];
+        let cycle = ColorCycle { range: (1, 3), speed: 2.0 };
+
+        assert_eq!(cycle_palette(&palette, &[cycle], 0.0), palette);
+        assert_eq!(cycle_palette(&palette, &[cycle], 0.5), vec![WHITE, BLUE, RED, GREEN]);
+        assert_eq!(cycle_palette(&palette, &[cycle], 1.5), palette);
+
+        let backwards = ColorCycle { range: (1, 3), speed: -2.0 };
+        assert_eq!(cycle_palette(&palette, &[backwards], 0.5), vec![WHITE, GREEN, BLUE, RED]);
+
+        let too_long = ColorCycle { range: (2, 200), speed: 2.0 };
+        assert_eq!(cycle_palette(&palette, &[too_long], 0.5), vec![WHITE, RED, BLUE, GREEN]);
+    }
+
+    #[test]
+    fn test_apply_color_cycles() {
+        let mut anim = indexed_animation(&[30, 10]);
+        apply_color_cycles(&mut anim, &[ColorCycle { range: (1, 3), speed: 10.0 }]);
+        assert_eq!(first_pixels(&anim), vec![(RED, 10), (BLUE, 10), (GREEN, 10), (RED, 10)]);
+        assert!(!anim.grayscale);
+
+        //Without palette, the animation stays as it is
+        let mut anim = indexed_animation(&[30]);
+        anim.palette = None;
+        apply_color_cycles(&mut anim, &[ColorCycle { range: (1, 3), speed: 5.0 }]);
+        assert_eq!(anim.frames.len(), 1);
+    }
+
+    #[test]
+    fn test_apply_color_cycles_long_frame() {
+        //A hold of 5 s would be 250 steps of 20 ms
+        let mut anim = indexed_animation(&[500]);
+        apply_color_cycles(&mut anim, &[ColorCycle { range: (1, 3), speed: 50.0 }]);
+        assert_eq!(anim.frames.len(), MAX_STEPS as usize);
+        assert!(anim.frames.iter().all(|frame| frame.delay == 20));
+        assert_eq!(anim.frames.iter().map(|frame| frame.delay as u32).sum::<u32>(), 500);
+    }
+}
@@ -0,0 +1,193 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::clock::{Clock, VirtualClock};
+use crate::color::{BLUE, Color, GREEN, RED, WHITE};
+use crate::gif::{Animation, animation_from_frames, Frame, frame_is_grayscale, HEIGHT, Pixel, WIDTH};
+use crate::gif::text::TEXT_MAGIC;
+use crate::renderer::{play_frames, Renderer};
+
+impl Frame {
+    /// Create a frame for test animations. Whether it's grayscale is checked by its pixels
+    ///
+    /// # Input
+    /// * `pixels`: All pixels of the frame
+    /// * `delay`: The delay of the frame
+    pub fn from_pixels(pixels: [[Pixel; WIDTH]; HEIGHT], delay: u16) -> Self {
+        let mut frame = Frame { pixels, delay, indices: None, grayscale: true };
+        frame.grayscale = frame_is_grayscale(&frame);
+        frame
+    }
+
+    /// Create a frame, whose pixels are all transparent black, for test animations
+    ///
+    /// # Input
+    /// The delay of the frame
+    pub fn blank(delay: u16) -> Self {
+        Frame::from_pixels([[Pixel { r: 0, g: 0, b: 0, a: 0 }; WIDTH]; HEIGHT], delay)
+    }
+
+    /// Create a black frame with a few colored pixels for test animations
+    ///
+    /// # Input
+    /// * `dots`: The x and y position of every colored pixel and its color
+    /// * `delay`: The delay of the frame
+    pub fn dotted(dots: &[(usize, usize, Color)], delay: u16) -> Self {
+        let mut pixels = [[Pixel { r: 0, g: 0, b: 0, a: u8::MAX }; WIDTH]; HEIGHT];
+        for (x, y, color) in dots {
+            pixels[*y][*x] = Pixel { r: color.r, g: color.g, b: color.b, a: u8::MAX };
+        }
+
+        Frame::from_pixels(pixels, delay)
+    }
+}
+
+/// Create a test animation of the given frames, which is grayscale if all of them are
+pub fn animation(frames: Vec<Frame>) -> Animation {
+    animation_from_frames(frames)
+}
+
+/// Create a test animation of blank frames with the given delays
+pub fn blank_animation(delays: &[u16]) -> Animation {
+    animation(delays.iter().map(|delay| Frame::blank(*delay)).collect())
+}
+
+/// Create an indexed test animation with the palette white, red, green and blue. The first pixel of every frame
+/// has the palette index 1, all others have none
+///
+/// # Input
+/// The delays of the frames
+pub fn indexed_animation(delays: &[u16]) -> Animation {
+    let frames = delays.iter()
+        .map(|delay| {
+            let mut indices = [[None; WIDTH]; HEIGHT];
+            indices[0][0] = Some(1);
+            Frame { indices: Some(indices), ..Frame::blank(*delay) }
+        })
+        .collect();
+
+    Animation { frames, grayscale: true, palette: Some(vec![WHITE, RED, GREEN, BLUE]) }
+}
+
+/// Create a test animation with a single frame, whose rows are filled with the given colors. Rows without
+/// a color are black
+pub fn striped_animation(rows: &[Color]) -> Animation {
+    let mut pixels = [[Pixel { r: 0, g: 0, b: 0, a: u8::MAX }; WIDTH]; HEIGHT];
+    for (row, color) in pixels.iter_mut().zip(rows) {
+        *row = [Pixel { r: color.r, g: color.g, b: color.b, a: u8::MAX }; WIDTH];
+    }
+
+    animation(vec![Frame::from_pixels(pixels, 100)])
+}
+
+/// Create a text animation, whose frames have a single white pixel in the top left corner
+///
+/// # Input
+/// The delays of the frames in milliseconds
+pub fn text_animation(delays: &[u32]) -> String {
+    let mut text = format!("{}\npalette\nx FFFFFF\n", TEXT_MAGIC);
+    for delay in delays {
+        text.push_str(&format!("frame {}\nx\n\n\n\n\n\n\n\n", delay));
+    }
+
+    text
+}
+
+/// Write a text animation like `text_animation()` to a file
+///
+/// # Input
+/// * `path`: Where the animation is written to
+/// * `delays`: The delays of the frames in milliseconds
+pub fn write_text_animation(path: &Path, delays: &[u32]) {
+    fs::write(path, text_animation(delays)).unwrap();
+}
+
+/// Renderer for tests, that remembers how it played which animation and when it showed which frame. It waits
+/// with a `VirtualClock`, so playing doesn't take any real time
+pub struct RecordingRenderer {
+    /// The clock the frames are shown with
+    pub clock: Arc<VirtualClock>,
+
+    /// How every animation was played, `"play"`, `"colored"` or `"rainbow"`
+    pub played: Vec<&'static str>,
+
+    /// Every animation, that was played
+    pub anims: Vec<Arc<Animation>>,
+
+    /// When which frame (by its delay) was shown
+    pub shown: Vec<(Duration, u16)>,
+
+    /// If it plays animations in colors, that move over time
+    pub rainbow: bool,
+
+    /// How often it wants to be refreshed while waiting, if at all
+    pub refresh_interval: Option<Duration>,
+
+    /// How often it was refreshed
+    pub refreshes: usize,
+}
+
+impl RecordingRenderer {
+    pub fn new() -> Self {
+        RecordingRenderer {
+            clock: Arc::new(VirtualClock::new()),
+            played: Vec::new(),
+            anims: Vec::new(),
+            shown: Vec::new(),
+            rainbow: false,
+            refresh_interval: None,
+            refreshes: 0,
+        }
+    }
+
+    /// Remember how an animation was played and show its frames
+    fn record(&mut self, how: &'static str, anim: Arc<Animation>) {
+        let clock = self.clock.clone();
+        play_frames(&anim.frames, clock.as_ref(), |_, frame| self.shown.push((clock.now(), frame.delay)));
+
+        self.played.push(how);
+        self.anims.push(anim);
+    }
+}
+
+impl Default for RecordingRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer for RecordingRenderer {
+    fn play(&mut self, anim: Arc<Animation>) {
+        self.record("play", anim);
+    }
+
+    fn play_colored(&mut self, anim: Arc<Animation>, _color: &Color) {
+        self.record("colored", anim);
+    }
+
+    fn clear(&mut self) {}
+
+    fn print_config(&self) {}
+
+    fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    fn refresh_interval(&self) -> Option<Duration> {
+        self.refresh_interval
+    }
+
+    fn refresh(&mut self) {
+        self.refreshes += 1;
+    }
+
+    fn play_rainbow(&mut self, anim: &Arc<Animation>) -> bool {
+        if self.rainbow {
+            self.record("rainbow", anim.clone());
+        }
+
+        self.rainbow
+    }
+}
@@ -0,0 +1,251 @@
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use log::info;
+use png::Transformations;
+
+use crate::file_operations::{DataType, SpriteSheet};
+use crate::gif::{Animation, animation_from_frames, apng, delay_from_millis, Frame, frame_from_rgba, GifError, HEIGHT, WIDTH};
+
+/// The `data_type` sprite sheet descriptors need to have
+pub const SPRITE_SHEET_DATA_TYPE: &str = "sprite_sheet";
+
+/// The delay of every frame, if the descriptor doesn't contain any
+const DEFAULT_DELAY: u32 = 100;
+
+/// The most bytes of a JSON file, that are read to check if it's a descriptor. Bigger files are none
+const MAX_DESCRIPTOR_SIZE: u64 = 64 * 1024;
+
+/// Check if JSON data is a sprite sheet descriptor by its `data_type`
+///
+/// # Input
+/// All bytes of the file
+///
+/// # Output
+/// If the data is JSON with the `data_type` of sprite sheets
+pub fn is_sprite_sheet(data: &[u8]) -> bool {
+    serde_json::from_slice::<DataType>(data).is_ok_and(|json| json.data_type == SPRITE_SHEET_DATA_TYPE)
+}
+
+/// Check if a file is a sprite sheet descriptor by its extension and `data_type`
+///
+/// # Input
+/// A `Path` to the file
+///
+/// # Output
+/// If the file is JSON with the `data_type` of sprite sheets
+pub fn is_sprite_sheet_file(path: &Path) -> bool {
+    read_descriptor(path).is_some()
+}
+
+/// Get the image a sprite sheet descriptor refers to, so it isn't used as an animation on its own
+///
+/// # Input
+/// A `Path` to the descriptor
+///
+/// # Output
+/// The canonical path of the image or `None`, if the file isn't a sprite sheet descriptor or its image
+/// can't be found
+pub fn sheet_image(path: &Path) -> Option<PathBuf> {
+    let sheet: SpriteSheet = serde_json::from_slice(&read_descriptor(path)?).ok()?;
+    resolve_image_path(path.parent().unwrap_or(Path::new(".")), &sheet.image).ok()
+}
+
+/// Read a file, if it's a sprite sheet descriptor
+///
+/// # Input
+/// A `Path` to the file
+///
+/// # Output
+/// All bytes of the descriptor or `None`, if the file isn't one
+fn read_descriptor(path: &Path) -> Option<Vec<u8>> {
+    if path.extension().is_none_or(|ext| !ext.eq_ignore_ascii_case("json")) {
+        return None;
+    }
+
+    let mut data = Vec::new();
+    fs::File::open(path).ok()?.take(MAX_DESCRIPTOR_SIZE).read_to_end(&mut data).ok()?;
+    is_sprite_sheet(&data).then_some(data)
+}
+
+/// Resolve the image path of a descriptor within the directory of the descriptor. This way descriptors,
+/// e.g. ones sent over the network, can't read any other image of the device
+///
+/// # Input
+/// * `dir`: The directory of the descriptor
+/// * `image`: The image path of the descriptor, relative to the directory
+///
+/// # Output
+/// A `Result<PathBuf, GifError>` with the canonical path of the image, that fails when the image doesn't
+/// exist or is outside of the directory
+fn resolve_image_path(dir: &Path, image: &str) -> Result<PathBuf, GifError> {
+    let dir = dir.canonicalize()?;
+    let resolved = dir.join(image).canonicalize()?;
+    if !resolved.starts_with(&dir) {
+        return Err(GifError::SpriteSheet(format!("The image ({}) is outside of the directory of the descriptor", image)));
+    }
+
+    Ok(resolved)
+}
+
+/// Read an animation from a JSON sprite sheet descriptor and the PNG file it refers to
+///
+/// # Input
+/// * `data`: The content of the descriptor
+/// * `dir`: The directory of the descriptor, which the image path is relative to
+///
+/// # Output
+/// A `Result<Animation, GifError>`, that fails when:
+/// * The descriptor can't be parsed
+/// * The image can't be read or is outside of the directory
+/// * The frames aren't as big as TASBots display or don't fit into the image
+/// * The order or delays don't match the frames
+pub fn read_sprite_sheet(data: &[u8], dir: &Path) -> Result<Animation, GifError> {
+    let sheet: SpriteSheet = serde_json::from_slice(data)?;
+    if sheet.data_type != SPRITE_SHEET_DATA_TYPE {
+        return Err(GifError::SpriteSheet(format!("Unexpected data type \"{}\"", sheet.data_type)));
+    }
+    if sheet.frame_width as usize != WIDTH || sheet.frame_height as usize != HEIGHT {
+        return Err(GifError::Dimensions(sheet.frame_width, sheet.frame_height));
+    }
+
+    //Read image
+    let image_path = resolve_image_path(dir, &sheet.image)?;
+    info!("Read sprite sheet ({})", image_path.display());
+    let mut decoder = png::Decoder::new(fs::File::open(&image_path)?);
+    decoder.set_transformations(Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let output = reader.next_frame(&mut buffer)?;
+    let image = apng::to_rgba(&buffer[..output.buffer_size()], output.color_type);
+    let (image_width, image_height) = (output.width, output.height);
+
+    //Determine grid
+    let step_x = sheet.frame_width + sheet.spacing;
+    let step_y = sheet.frame_height + sheet.spacing;
+    let fitting_columns = (image_width.saturating_sub(2 * sheet.margin) + sheet.spacing) / step_x;
+    let fitting_rows = (image_height.saturating_sub(2 * sheet.margin) + sheet.spacing) / step_y;
+    let columns = sheet.columns.unwrap_or(fitting_columns);
+    let rows = sheet.rows.unwrap_or(fitting_rows);
+    if columns == 0 || rows == 0 || columns > fitting_columns || rows > fitting_rows {
+        return Err(GifError::SpriteSheet(format!("A grid of {}x{} frames doesn't fit into the image", columns, rows)));
+    }
+
+    let frame_count = (columns * rows) as usize;
+    let order = sheet.order.unwrap_or_else(|| (0..frame_count).collect());
+    let delays = match sheet.delays.len() {
+        0 => vec![DEFAULT_DELAY; order.len()],
+        1 => vec![sheet.delays[0]; order.len()],
+        len if len == order.len() => sheet.delays,
+        len => return Err(GifError::SpriteSheet(format!("Expected 1 or {} delays, but got {}", order.len(), len))),
+    };
+
+    //Cut frames out of the image
+    let mut frames: Vec<Frame> = Vec::new();
+    for (index, delay) in order.iter().zip(delays) {
+        if *index >= frame_count {
+            return Err(GifError::SpriteSheet(format!("Frame {} is not part of the sheet", index)));
+        }
+
+        let left = (sheet.margin + (*index as u32 % columns) * step_x) as usize;
+        let top = (sheet.margin + (*index as u32 / columns) * step_y) as usize;
+        let mut rgba: Vec<u8> = Vec::with_capacity(WIDTH * HEIGHT * 4);
+        for y in top..top + HEIGHT {
+            let start = (y * image_width as usize + left) * 4;
+            rgba.extend_from_slice(&image[start..start + WIDTH * 4]);
+        }
+
+        frames.push(frame_from_rgba(&rgba, delay_from_millis(delay)));
+    }
+
+    //Repeat all frames as often as wanted
+    let loop_count = sheet.loop_count.unwrap_or(1).max(1) as usize;
+    let frames = frames.iter().cloned().cycle().take(frames.len() * loop_count).collect();
+
+    Ok(animation_from_frames(frames))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use png::{BitDepth, ColorType, Encoder};
+    use tempdir::TempDir;
+
+    use crate::gif::read_animation;
+
+    use super::*;
+
+    /// Write a 2x2 sheet, where every frame has a single white pixel at the position of its index
+    fn write_sheet(dir: &Path) {
+        let (width, height) = (WIDTH * 2 + 1, HEIGHT * 2 + 1);
+        let mut image = vec![0u8; width * height * 3];
+        for index in 0..4 {
+            let x = (index % 2) * (WIDTH + 1) + index;
+            let y = (index / 2) * (HEIGHT + 1);
+            image[(y * width + x) * 3..][..3].copy_from_slice(&[255, 255, 255]);
+        }
+
+        let file = fs::File::create(dir.join("sheet.png")).unwrap();
+        let mut encoder = Encoder::new(file, width as u32, height as u32);
+        encoder.set_color(ColorType::Rgb);
+        encoder.set_depth(BitDepth::Eight);
+        encoder.write_header().unwrap().write_image_data(&image).unwrap();
+    }
+
+    #[test]
+    fn test_read_sprite_sheet() {
+        let temp_dir = TempDir::new("test_read_sprite_sheet").unwrap();
+        write_sheet(temp_dir.path());
+
+        let descriptor = r#"{
+            "data_type": "sprite_sheet",
+            "image": "sheet.png",
+            "frame_width": 28,
+            "frame_height": 8,
+            "spacing": 1,
+            "order": [3, 0, 1],
+            "delays": [50, 100, 200],
+            "loop_count": 2
+        }"#;
+        let path: PathBuf = temp_dir.path().join("sheet.json");
+        fs::write(&path, descriptor).unwrap();
+
+        let anim = read_animation(&path).unwrap();
+        assert_eq!(anim.frames.len(), 6);
+        assert!(anim.grayscale);
+        assert_eq!(anim.frames.iter().map(|f| f.delay).collect::<Vec<u16>>(), vec![5, 10, 20, 5, 10, 20]);
+        assert_eq!(anim.frames[0].pixels[0][3].r, 255);
+        assert_eq!(anim.frames[1].pixels[0][0].r, 255);
+        assert_eq!(anim.frames[2].pixels[0][1].r, 255);
+        assert_eq!(anim.frames[2].pixels[0][0].r, 0);
+    }
+
+    #[test]
+    fn test_read_sprite_sheet_errors() {
+        let temp_dir = TempDir::new("test_read_sprite_sheet_errors").unwrap();
+        write_sheet(temp_dir.path());
+
+        let wrong_size = br#"{"data_type": "sprite_sheet", "image": "sheet.png", "frame_width": 8, "frame_height": 8}"#;
+        assert!(matches!(read_sprite_sheet(wrong_size, temp_dir.path()), Err(GifError::Dimensions(8, 8))));
+
+        let too_many_rows = br#"{"data_type": "sprite_sheet", "image": "sheet.png", "frame_width": 28, "frame_height": 8, "spacing": 1, "rows": 3}"#;
+        assert!(read_sprite_sheet(too_many_rows, temp_dir.path()).is_err());
+
+        let wrong_delays = br#"{"data_type": "sprite_sheet", "image": "sheet.png", "frame_width": 28, "frame_height": 8, "spacing": 1, "delays": [1, 2]}"#;
+        assert!(read_sprite_sheet(wrong_delays, temp_dir.path()).is_err());
+
+        let wrong_type = br#"{"data_type": "playlist", "image": "sheet.png", "frame_width": 28, "frame_height": 8}"#;
+        assert!(read_sprite_sheet(wrong_type, temp_dir.path()).is_err());
+
+        //Images outside of the directory of the descriptor can't be read
+        let sub_dir = temp_dir.path().join("sheets");
+        fs::create_dir(&sub_dir).unwrap();
+        let parent = br#"{"data_type": "sprite_sheet", "image": "../sheet.png", "frame_width": 28, "frame_height": 8, "spacing": 1}"#;
+        assert!(matches!(read_sprite_sheet(parent, &sub_dir), Err(GifError::SpriteSheet(_))));
+        let absolute = format!(r#"{{"data_type": "sprite_sheet", "image": "{}", "frame_width": 28, "frame_height": 8, "spacing": 1}}"#,
+                               temp_dir.path().join("sheet.png").display());
+        assert!(matches!(read_sprite_sheet(absolute.as_bytes(), &sub_dir), Err(GifError::SpriteSheet(_))));
+    }
+}
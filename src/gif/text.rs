@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::color::{BLACK, Color};
+use crate::gif::{Animation, animation_from_frames, delay_from_millis, Frame, frame_is_grayscale, GifError, HEIGHT, Pixel, WIDTH};
+
+/// The first line of every text animation, which is used to detect the format
+pub const TEXT_MAGIC: &str = "tasbot-eyes-text";
+
+/// Lines outside of the rows of a frame, that start with this, are comments. A palette entry can't look like this,
+/// as no color starts with a slash
+const COMMENT_PREFIX: &str = "//";
+
+/// Characters, that are always black without being listed in the palette
+const BLACK_CHARACTERS: [char; 2] = [' ', '.'];
+
+/// Read an animation from a human editable text file. It looks like this:
+///
+/// ```text
+/// tasbot-eyes-text
+/// // Comments start with two slashes
+/// palette
+/// x FFFFFF
+/// r FF0000
+/// frame 100
+///   xxxx          rrrr
+/// (... 8 rows with 28 characters each ...)
+/// frame 50
+/// (...)
+/// ```
+///
+/// The palette maps a character to a hex color, spaces and dots are black unless they're in the
+/// palette. Every frame starts with a header containing its delay in milliseconds. Rows may be shorter
+/// than 28 characters, the rest is filled with black. Comments can be anywhere but within the rows of a frame
+///
+/// # Input
+/// The content of the text file
+///
+/// # Output
+/// A `Result<Animation, GifError>`, that fails when the text is malformed
+pub fn read_text(data: &str) -> Result<Animation, GifError> {
+    let mut lines = data.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line));
+
+    match lines.next() {
+        Some((_, line)) if line.trim() == TEXT_MAGIC => {}
+        _ => return Err(text_error(1, "Missing header")),
+    }
+
+    let mut palette: HashMap<char, Color> = HashMap::new();
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut in_palette = false;
+
+    while let Some((number, line)) = lines.next() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with(COMMENT_PREFIX) {
+            continue;
+        }
+
+        if trimmed == "palette" {
+            in_palette = true;
+        } else if let Some(delay) = trimmed.strip_prefix("frame") {
+            in_palette = false;
+            let delay: u32 = delay.trim().parse().map_err(|_| text_error(number, "Invalid frame delay"))?;
+
+            let mut frame = Frame {
+                pixels: [[Pixel { r: 0, g: 0, b: 0, a: u8::MAX }; WIDTH]; HEIGHT],
+                delay: delay_from_millis(delay),
+                indices: None,
+                grayscale: true,
+            };
+
+            for y in 0..HEIGHT {
+                let (number, row) = lines.next().ok_or_else(|| text_error(number, "Frame has less than 8 rows"))?;
+                if row.chars().count() > WIDTH {
+                    return Err(text_error(number, "Row is longer than 28 characters"));
+                }
+
+                for (x, c) in row.chars().enumerate() {
+                    let color = if BLACK_CHARACTERS.contains(&c) && !palette.contains_key(&c) {
+                        BLACK
+                    } else {
+                        *palette.get(&c).ok_or_else(|| text_error(number, &format!("Character '{}' is not in the palette", c)))?
+                    };
+                    frame.pixels[y][x] = Pixel { r: color.r, g: color.g, b: color.b, a: u8::MAX };
+                }
+            }
+
+            frame.grayscale = frame_is_grayscale(&frame);
+            frames.push(frame);
+        } else if in_palette {
+            let mut chars = line.trim_start().chars();
+            let character = chars.next().unwrap_or(' ');
+            let color = Color::from_str(chars.as_str()).map_err(|e| text_error(number, &e))?;
+            palette.insert(character, color);
+        } else {
+            return Err(text_error(number, "Expected \"palette\" or \"frame <delay>\""));
+        }
+    }
+
+    if frames.is_empty() {
+        return Err(text_error(data.lines().count(), "No frames found"));
+    }
+
+    Ok(animation_from_frames(frames))
+}
+
+/// Create an error for a given line of the text
+fn text_error(line: usize, message: &str) -> GifError {
+    GifError::Text(format!("Line {}: {}", line, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "tasbot-eyes-text
+// Three colors
+palette
+x FFFFFF
+r FF0000
+# 0000FF
+frame 100
+xx
+
+  rr
+
+#
+.
+..
+xxxxxxxxxxxxxxxxxxxxxxxxxxxx
+// The second frame
+frame 50
+x
+
+
+
+
+
+
+
+";
+
+    #[test]
+    fn test_read_text() {
+        let anim = read_text(TEXT).unwrap();
+        assert_eq!(anim.frames.len(), 2);
+        assert!(!anim.grayscale);
+        assert!(!anim.frames[0].grayscale);
+        assert!(anim.frames[1].grayscale);
+        assert_eq!(anim.frames[0].delay, 10);
+        assert_eq!(anim.frames[1].delay, 5);
+        assert_eq!(anim.frames[0].pixels[0][1], Pixel { r: 255, g: 255, b: 255, a: 255 });
+        assert_eq!(anim.frames[0].pixels[2][2], Pixel { r: 255, g: 0, b: 0, a: 255 });
+        assert_eq!(anim.frames[0].pixels[4][0], Pixel { r: 0, g: 0, b: 255, a: 255 });
+        assert_eq!(anim.frames[0].pixels[7][27], Pixel { r: 255, g: 255, b: 255, a: 255 });
+        assert_eq!(anim.frames[1].pixels[0][1].r, 0);
+    }
+
+    #[test]
+    fn test_read_text_errors() {
+        assert!(read_text("frame 100\n").is_err());
+        assert!(read_text("tasbot-eyes-text\nframe 100\nxx\n").is_err());
+        assert!(read_text("tasbot-eyes-text\nframe abc\n").is_err());
+        assert!(read_text("tasbot-eyes-text\n").is_err());
+    }
+}
@@ -0,0 +1,87 @@
+use std::io::Cursor;
+
+use image_webp::{DecodingError, WebPDecoder};
+
+use crate::gif::{Animation, animation_from_frames, delay_from_millis, Frame, frame_from_rgba, GifError, HEIGHT, WIDTH};
+
+/// Read an animation from the bytes of a WebP file. The decoder already composes the frames of
+/// animated WebPs, still WebPs result in an `Animation` with a single frame
+///
+/// # Input
+/// The content of the WebP file
+///
+/// # Output
+/// A `Result<Animation, GifError>`, that fails when:
+/// * The WebP can't be decoded
+/// * The WebP is not as big as TASBots display
+pub fn read_webp(data: &[u8]) -> Result<Animation, GifError> {
+    let mut decoder = WebPDecoder::new(Cursor::new(data))?;
+
+    let (width, height) = decoder.dimensions();
+    if width as usize != WIDTH || height as usize != HEIGHT {
+        return Err(GifError::Dimensions(width, height));
+    }
+
+    let has_alpha = decoder.has_alpha();
+    let mut buffer = vec![0; decoder.output_buffer_size().ok_or(DecodingError::ImageTooLarge)?];
+    let mut frames: Vec<Frame> = Vec::new();
+
+    if decoder.is_animated() {
+        loop {
+            match decoder.read_frame(&mut buffer) {
+                Ok(duration) => frames.push(frame_from_rgba(&to_rgba(&buffer, has_alpha), delay_from_millis(duration))),
+                Err(DecodingError::NoMoreFrames) => break,
+                Err(e) => return Err(GifError::from(e)),
+            }
+        }
+    } else {
+        decoder.read_image(&mut buffer)?;
+        frames.push(frame_from_rgba(&to_rgba(&buffer, has_alpha), 0));
+    }
+
+    Ok(animation_from_frames(frames))
+}
+
+/// Convert the output of the WebP decoder to RGBA values
+///
+/// # Input
+/// * `data`: The decoded samples, either RGB or RGBA
+/// * `has_alpha`: If the samples contain an alpha channel
+///
+/// # Output
+/// The samples as RGBA values
+fn to_rgba(data: &[u8], has_alpha: bool) -> Vec<u8> {
+    if has_alpha {
+        data.to_vec()
+    } else {
+        data.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], u8::MAX]).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image_webp::{ColorType, WebPEncoder};
+
+    use super::*;
+
+    #[test]
+    fn test_read_webp() {
+        let mut rgb = vec![0u8; WIDTH * HEIGHT * 3];
+        rgb[0..3].copy_from_slice(&[255, 255, 255]);
+
+        let mut data: Vec<u8> = Vec::new();
+        WebPEncoder::new(&mut data).encode(&rgb, WIDTH as u32, HEIGHT as u32, ColorType::Rgb8).unwrap();
+
+        let anim = read_webp(&data).unwrap();
+        assert_eq!(anim.frames.len(), 1);
+        assert!(anim.grayscale);
+        assert_eq!(anim.frames[0].pixels[0][0].r, 255);
+        assert_eq!(anim.frames[0].pixels[0][1].r, 0);
+    }
+
+    #[test]
+    fn test_to_rgba() {
+        assert_eq!(to_rgba(&[1, 2, 3, 4, 5, 6], false), vec![1, 2, 3, 255, 4, 5, 6, 255]);
+        assert_eq!(to_rgba(&[1, 2, 3, 4], true), vec![1, 2, 3, 4]);
+    }
+}
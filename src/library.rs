@@ -0,0 +1,327 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use log::{info, warn};
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use crate::file_operations::{files_in_directory, FileOperationsError, modified, read_metadata};
+use crate::gif::AnimationFormat;
+use crate::gif::sprite_sheet::{is_sprite_sheet_file, sheet_image};
+
+/// File extensions of all formats an animation can be read from
+pub const SUPPORTED_EXTENSIONS: [&str; 8] = ["gif", "png", "apng", "webp", "aseprite", "ase", "txt", "json"];
+
+/// Names of files and folders, that are always ignored. `*` matches any amount of characters, `?` a single one
+pub const DEFAULT_IGNORE_PATTERNS: [&str; 4] = [".*", "README*", "LICENSE*", "Thumbs.db"];
+
+#[derive(Clone, PartialEq, Debug)]
+/// A single animation of the `Library`
+pub struct LibraryEntry {
+    /// The path to the animation
+    pub path: PathBuf,
+
+    /// The names of all subfolders the animation is in, followed by the tags of its metadata
+    pub tags: Vec<String>,
+
+    /// How likely the animation gets chosen, see `file_operations::Metadata`
+    pub weight: f32,
+}
+
+impl LibraryEntry {
+    /// Check if the animation has a tag. The comparison ignores the case
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+}
+
+/// All animations within a directory tree. Every subfolder is a category and used as tag,
+/// e.g. `others/happy/smile.gif` is tagged with `happy`
+pub struct Library {
+    /// All animations that were found
+    pub entries: Vec<LibraryEntry>,
+}
+
+impl Library {
+    /// Scan a directory recursively for animations. Only files with supported extensions are added,
+    /// metadata sidecar files, the images of sprite sheets as well as files and folders matching an ignore
+    /// pattern are skipped
+    ///
+    /// # Input
+    /// * `root`: The directory that is to scan
+    /// * `ignore_patterns`: Additional patterns of file or folder names, that are to ignore
+    ///
+    /// # Output
+    /// A `Result<Library, FileOperationsError>`, that fails when the root directory can't be read
+    pub fn scan(root: &Path, ignore_patterns: &[String]) -> Result<Library, FileOperationsError> {
+        let mut patterns: Vec<&str> = DEFAULT_IGNORE_PATTERNS.to_vec();
+        patterns.extend(ignore_patterns.iter().map(|p| p.as_str()));
+
+        let mut entries: Vec<LibraryEntry> = Vec::new();
+        scan_directory(root, &[], &patterns, &mut entries)?;
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+        //The images of sprite sheets are part of their descriptors, not animations on their own
+        let sheet_images: Vec<PathBuf> = entries.iter().filter_map(|entry| sheet_image(&entry.path)).collect();
+        if !sheet_images.is_empty() {
+            entries.retain(|entry| fs::canonicalize(&entry.path).map_or(true, |path| !sheet_images.contains(&path)));
+        }
+
+        info!("Found {} animation/animations in ({})", entries.len(), root.display());
+        Ok(Library { entries })
+    }
+
+    /// Get all animations with a tag
+    pub fn with_tag(&self, tag: &str) -> Vec<&LibraryEntry> {
+        self.entries.iter().filter(|entry| entry.has_tag(tag)).collect()
+    }
+
+    /// Get all tags, that are used by at least one animation
+    pub fn tags(&self) -> BTreeSet<String> {
+        self.entries.iter().flat_map(|entry| entry.tags.iter().cloned()).collect()
+    }
+
+    /// Choose a random animation, respecting the weight of the animations
+    ///
+    /// # Input
+    /// * `tag`: Only choose from animations with this tag, if set
+    /// * `rng`: The random number generator to use
+    ///
+    /// # Output
+    /// The chosen `LibraryEntry` or `None`, if there's no (weighted) animation to choose from
+    pub fn random<R: Rng>(&self, tag: Option<&str>, rng: &mut R) -> Option<&LibraryEntry> {
+        let candidates: Vec<&LibraryEntry> = match tag {
+            None => self.entries.iter().collect(),
+            Some(tag) => self.with_tag(tag),
+        };
+
+        candidates.choose_weighted(rng, |entry| entry.weight.max(0.0)).ok().copied()
+    }
+}
+
+/// A `Library`, that is only scanned again when something in its directory was modified
+pub struct CachedLibrary {
+    /// The directory of the library
+    root: PathBuf,
+
+    /// Additional patterns of file or folder names, that are to ignore
+    ignore_patterns: Vec<String>,
+
+    /// The scanned library and when anything in its directory was modified at the time of the scan
+    scanned: Option<(Library, Option<SystemTime>)>,
+}
+
+impl CachedLibrary {
+    /// Create a cache for a library, that is scanned on first use. See `Library::scan()` for the input
+    pub fn new(root: &Path, ignore_patterns: &[String]) -> Self {
+        CachedLibrary { root: root.to_path_buf(), ignore_patterns: ignore_patterns.to_vec(), scanned: None }
+    }
+
+    /// Get the library. It's scanned again, if anything in its directory was modified since the last scan
+    ///
+    /// # Output
+    /// A `Result<&Library, FileOperationsError>`, that fails when the root directory can't be read
+    pub fn get(&mut self) -> Result<&Library, FileOperationsError> {
+        let modified = last_modified(&self.root);
+        if self.scanned.as_ref().is_none_or(|(_, scanned)| *scanned != modified) {
+            self.scanned = Some((Library::scan(&self.root, &self.ignore_patterns)?, modified));
+        }
+
+        Ok(&self.scanned.as_ref().unwrap().0)
+    }
+}
+
+/// Get when anything in a directory was modified the last time: the directory itself, its files or its
+/// subfolders. Only the modification times are read, so this is a lot cheaper than a scan
+///
+/// # Input
+/// The directory that is to check
+///
+/// # Output
+/// The latest modification time, `None` if the directory can't be read
+fn last_modified(dir: &Path) -> Option<SystemTime> {
+    let mut latest = modified(dir);
+    for path in fs::read_dir(dir).into_iter().flatten().filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+        let time = if path.is_dir() { last_modified(&path) } else { modified(&path) };
+        latest = latest.max(time);
+    }
+
+    latest
+}
+
+/// Add all animations of a directory and its subfolders to the entries
+///
+/// # Input
+/// * `dir`: The directory that is to scan
+/// * `categories`: The names of the folders from the root to this directory
+/// * `patterns`: All ignore patterns
+/// * `entries`: The list the animations are added to
+fn scan_directory(dir: &Path, categories: &[String], patterns: &[&str], entries: &mut Vec<LibraryEntry>) -> Result<(), FileOperationsError> {
+    for file in files_in_directory(dir)? {
+        if is_ignored(&file, patterns) || !is_animation(&file) {
+            continue;
+        }
+
+        let mut tags = categories.to_vec();
+        let metadata = read_metadata(&file);
+        tags.extend(metadata.tags);
+        entries.push(LibraryEntry { path: file, tags, weight: metadata.weight });
+    }
+
+    for subdir in fs::read_dir(dir)?.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+        if !subdir.is_dir() || is_ignored(&subdir, patterns) {
+            continue;
+        }
+
+        let mut sub_categories = categories.to_vec();
+        sub_categories.push(subdir.file_name().unwrap_or_default().to_string_lossy().to_string());
+        if let Err(e) = scan_directory(&subdir, &sub_categories, patterns, entries) {
+            warn!("Can't read directory ({}): {}", subdir.display(), e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Check if a file or folder name matches any of the ignore patterns
+fn is_ignored(path: &Path, patterns: &[&str]) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    patterns.iter().any(|pattern| matches_pattern(pattern, &name))
+}
+
+/// Check if a file is an animation. Text and JSON files are only animations, if their content says so, so
+/// metadata sidecar files are no animations
+fn is_animation(path: &Path) -> bool {
+    let extension = path.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+    if !SUPPORTED_EXTENSIONS.contains(&extension.as_str()) {
+        return false;
+    }
+
+    match extension.as_str() {
+        "txt" => read_head(path).is_some_and(|head| AnimationFormat::detect(&head) == Some(AnimationFormat::Text)),
+        "json" => is_sprite_sheet_file(path),
+        _ => true,
+    }
+}
+
+/// Read the first bytes of a file, which are enough to detect its format
+fn read_head(path: &Path) -> Option<Vec<u8>> {
+    let mut data = Vec::new();
+    fs::File::open(path).ok()?.take(64).read_to_end(&mut data).ok()?;
+    Some(data)
+}
+
+/// Match a name against a simple pattern, where `*` matches any amount of characters and `?` a single one
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match (pattern.first(), name.first()) {
+            (None, None) => true,
+            (Some('*'), _) => matches(&pattern[1..], name) || (!name.is_empty() && matches(pattern, &name[1..])),
+            (Some('?'), Some(_)) => matches(&pattern[1..], &name[1..]),
+            (Some(p), Some(n)) => p == n && matches(&pattern[1..], &name[1..]),
+            _ => false,
+        }
+    }
+
+    matches(&pattern, &name)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_scan() {
+        let temp_dir = TempDir::new("test_scan").unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("happy/party")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::create_dir_all(root.join("drafts")).unwrap();
+
+        for file in ["eyes.gif", "happy/smile.png", "happy/party/confetti.webp", ".git/hidden.gif", "drafts/wip.gif",
+            "README.md", ".DS_Store", "happy/notes.txt", "happy/smile.json"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+        fs::write(root.join("happy/smile.json"), r#"{"tags": ["Smile"], "weight": 3.0}"#).unwrap();
+        fs::write(root.join("palette.json"), r#"{"data_type": "palette", "colors": ["FF0000"]}"#).unwrap();
+        fs::write(root.join("blink.txt"), "tasbot-eyes-text\n").unwrap();
+
+        //A sprite sheet named like its image
+        fs::write(root.join("sheet.png"), "").unwrap();
+        fs::write(root.join("sheet.json"), r#"{"data_type": "sprite_sheet", "image": "sheet.png", "frame_width": 28, "frame_height": 8}"#).unwrap();
+
+        let library = Library::scan(root, &["drafts".to_owned()]).unwrap();
+        let paths: Vec<PathBuf> = library.entries.iter().map(|entry| entry.path.clone()).collect();
+        assert_eq!(paths, vec![
+            root.join("blink.txt"),
+            root.join("eyes.gif"),
+            root.join("happy/party/confetti.webp"),
+            root.join("happy/smile.png"),
+            root.join("sheet.json"),
+        ]);
+
+        assert_eq!(library.entries[2].tags, vec!["happy", "party"]);
+        assert_eq!(library.entries[3].tags, vec!["happy", "Smile"]);
+        assert_eq!(library.entries[3].weight, 3.0);
+        assert_eq!(library.tags().into_iter().collect::<Vec<String>>(), vec!["Smile", "happy", "party"]);
+    }
+
+    #[test]
+    fn test_cached_library() {
+        let temp_dir = TempDir::new("test_cached_library").unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("happy")).unwrap();
+        fs::write(root.join("eyes.gif"), "").unwrap();
+
+        let mut cached = CachedLibrary::new(root, &[]);
+        assert_eq!(cached.get().unwrap().entries.len(), 1);
+
+        //Without a change, the old scan is used
+        let scanned = cached.scanned.as_ref().unwrap().1;
+        assert_eq!(cached.get().unwrap().entries.len(), 1);
+        assert_eq!(cached.scanned.as_ref().unwrap().1, scanned);
+
+        //A changed subfolder is found
+        let smile = root.join("happy/smile.gif");
+        fs::write(&smile, "").unwrap();
+        fs::File::options().write(true).open(&smile).unwrap().set_modified(SystemTime::now() + std::time::Duration::from_secs(60)).unwrap();
+        assert_eq!(cached.get().unwrap().entries.len(), 2);
+
+        assert!(CachedLibrary::new(&root.join("missing"), &[]).get().is_err());
+    }
+
+    #[test]
+    fn test_random() {
+        let entry = |name: &str, tag: &str, weight: f32| LibraryEntry { path: PathBuf::from(name), tags: vec![tag.to_owned()], weight };
+        let library = Library {
+            entries: vec![entry("a.gif", "happy", 1.0), entry("b.gif", "sad", 1.0), entry("c.gif", "happy", 0.0)],
+        };
+
+        let mut rng = thread_rng();
+        for _ in 0..10 {
+            assert_eq!(library.random(Some("HAPPY"), &mut rng).unwrap().path, PathBuf::from("a.gif"));
+        }
+        assert_eq!(library.with_tag("happy").len(), 2);
+        assert!(library.random(Some("unknown"), &mut rng).is_none());
+        assert!(library.random(None, &mut rng).is_some());
+    }
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern(".*", ".DS_Store"));
+        assert!(matches_pattern("README*", "README.md"));
+        assert!(matches_pattern("frame_??.gif", "frame_01.gif"));
+        assert!(matches_pattern("*", ""));
+        assert!(!matches_pattern("frame_??.gif", "frame_1.gif"));
+        assert!(!matches_pattern(".*", "eyes.gif"));
+    }
+}
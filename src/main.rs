@@ -1,6 +1,7 @@
 use std::{env, thread};
 use std::fmt::format;
-use std::path::PathBuf;
+use std::process::ExitCode;
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicBool, Ordering};
 
@@ -9,15 +10,25 @@ use log::{error, info, LevelFilter, warn};
 use rs_ws281x::StripType::Ws2812;
 
 use crate::arguments::{ARGUMENTS, fallback_arguments, init_arguments, RendererType};
-use crate::color::init_color_palette;
+use crate::clock::{Clock, SystemClock};
+use crate::color::{Color, init_color_palette};
+use crate::color::extract::extract_palette;
+use crate::color_mapping::ColorMapper;
+use crate::export::export_from_path;
 use crate::led::build_controller;
 use crate::logging::CONSOLE_LOGGER;
 use crate::network::start_recv_file_server;
+use crate::playback::QueuedAnimation;
+use crate::rainbow::RainbowPlayer;
+use crate::random::init_random;
+use crate::renderer::compiled::CompiledCache;
 use crate::renderer::console::ConsoleRendererSettings;
 use crate::renderer::led_matrix::{get_led_matrix_config, LEDMatrixRenderer};
 use crate::renderer::silent::SilentRendererSettings;
 use crate::renderer::tasbot_eyes::{get_tasbot_eye_config, SCREEN_HEIGHT, SCREEN_WIDTH, TASBotRendererSettings};
+use crate::settings::init_settings;
 use crate::tasbot::start_eyes;
+use crate::validation::check_paths;
 
 /// Contain operations, that read or write to a permanent storage
 mod file_operations;
@@ -37,15 +48,45 @@ mod tasbot;
 /// The color structure and other conversion code
 mod color;
 
+/// Map the gray levels of animations onto an overwrite color
+mod color_mapping;
+
+/// Color animations by a hue, that moves over time
+mod rainbow;
+
 /// Define and parse command line arguments
 mod arguments;
 
+/// The clock everything waits with, which can be replaced for tests
+mod clock;
+
 /// Contains the hardware configuration structure and a builder for it
 mod led;
 
 /// Network code such as the animation injection thread are here
 mod network;
 
+/// Scan the animation folders for animations and their tags
+mod library;
+
+/// The seeded random number generator everything random uses
+mod random;
+
+/// Modifiers, how the frames of an animation are played
+mod playback;
+
+/// Strategies, how the next animation is chosen
+mod selection;
+
+/// Settings, that can be changed while running
+mod settings;
+
+/// Check animations for problems
+mod validation;
+
+/// Write animations to other formats, like GIF, PNG frames or C headers
+mod export;
+
 //todo: clear on exit as argument or clear on exit fast
 //todo: cfg for arm not working
 //todo: reorder files, lol
@@ -64,7 +105,7 @@ pub const ENV_LOG_LEVEL: &str = "TASBOT_EYES_LOG_LEVEL";
 /// The fallback log level, when the environmental variable cannot be found
 pub const LOG_LEVEL_FALLBACK: &str = "warn";
 
-fn main() {
+fn main() -> ExitCode {
     let running = Arc::new(AtomicBool::new(true));
 
     //Setup logger
@@ -76,8 +117,48 @@ fn main() {
     let fallback_args = fallback_arguments();
     let args = ARGUMENTS.get().unwrap_or(&fallback_args);
 
+    //Tools just run once and exit with whether they succeeded
+    if let RendererType::Export { input, output, format, name } = &args.renderer {
+        return match export_from_path(input, output, *format, name.as_deref()) {
+            Ok(_) => ExitCode::SUCCESS,
+            Err(e) => {
+                error!("Can't export animation: {}", e);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    if let RendererType::Check { paths } = &args.renderer {
+        let issues = check_paths(paths, &args.ignore_patterns);
+        for issue in &issues {
+            println!("{}", issue);
+        }
+        println!("Found {} issue/issues", issues.len());
+
+        return if issues.is_empty() { ExitCode::SUCCESS } else { ExitCode::FAILURE };
+    }
+
+    if let RendererType::ExtractPalette { paths, output, count } = &args.renderer {
+        return match extract_palette(paths, output, *count, &args.ignore_patterns) {
+            Ok(colors) => {
+                for color in &colors {
+                    println!("#{}", format!("{}", color).truecolor(color.r, color.g, color.b));
+                }
+                println!("Wrote {} color/colors to ({})", colors.len(), output.display());
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                error!("Can't extract palette: {}", e);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    //Seeded before the network thread starts, which derives its own random numbers from the seed
+    init_random(args.seed);
+
     //Setup queue and network thread
-    let queue: Arc<Mutex<Vec<PathBuf>>> = Arc::new(Mutex::new(Vec::new()));
+    let queue: Arc<Mutex<Vec<QueuedAnimation>>> = Arc::new(Mutex::new(Vec::new()));
     let queue_network = queue.clone();
     thread::spawn(move || {
         start_recv_file_server(queue_network);
@@ -85,6 +166,8 @@ fn main() {
 
     //Setup other stuff
     setup_sigint_handler(&running);
+    init_settings(args.playback_speed, args.default_color.as_deref().and_then(|color| Color::from_str(color).ok()));
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock::new());
     init_color_palette(&args.palette);
 
     //Check arguments and start with right renderer
@@ -95,6 +178,9 @@ fn main() {
         } => {
             let cli = ConsoleRendererSettings {
                 clear_console: clear.clone(),
+                clock: clock.clone(),
+                mapper: ColorMapper::from_arguments(),
+                rainbow: RainbowPlayer::from_arguments(),
             };
 
             start_eyes(cli, queue.clone(), running);
@@ -177,6 +263,7 @@ fn main() {
                                 controller,
                                 gamma_correction: *gamma_correction,
                                 gamma: g,
+                                clock: clock.clone(),
                             };
 
                             start_eyes(matrix, queue.clone(), running);
@@ -227,6 +314,10 @@ fn main() {
                         controller,
                         gamma_correction: *gamma_correction,
                         gamma: g,
+                        clock: clock.clone(),
+                        compiled: CompiledCache::default(),
+                        mapper: ColorMapper::from_arguments(),
+                        rainbow: RainbowPlayer::from_arguments(),
                     };
 
                     start_eyes(tasbot_eyes, queue.clone(), running);
@@ -238,10 +329,15 @@ fn main() {
         }
 
         RendererType::Silent => {
-            let silent = SilentRendererSettings {};
+            let silent = SilentRendererSettings { clock: clock.clone() };
             start_eyes(silent, queue.clone(), running);
         }
+
+        //Already handled before
+        RendererType::Export { .. } | RendererType::Check { .. } | RendererType::ExtractPalette { .. } => {}
     }
+
+    ExitCode::SUCCESS
 }
 
 ///Setup the handler that gets called, when the process receives an SIGINT
@@ -1,15 +1,22 @@
 use std::env::temp_dir;
 use std::io::{Read, Write};
 use std::net::{SocketAddr, TcpListener, TcpStream};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 
-use log::{error, info};
+use log::{error, info, warn};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::arguments::{ARGUMENTS, fallback_arguments};
+use crate::color::{Color, ColorError, load_color_palette, reload_color_palette, set_palette_colors};
+use crate::file_operations::{FileOperationsError, OTHER_PATH, PALETTE_PATH};
+use crate::gif::AnimationFormat;
+use crate::library::Library;
+use crate::playback::{Playback, QueuedAnimation};
+use crate::random::{init_thread_random, NETWORK_STREAM, with_rng};
+use crate::settings::{set_default_color, set_playback_speed};
 use crate::network::PlayMode::{Now, Queued};
 
 //todo: pub const QUEUE_PORT: u16 = 8080; //legacy support
@@ -24,16 +31,65 @@ pub enum NetworkError {
 
     #[error("An conversion error occurred: {0}")]
     Conversion(&'static str),
+
+    #[error("Can't read the animation library: {0}")]
+    Library(#[from] FileOperationsError),
+
+    #[error("No animation is tagged with \"{0}\"")]
+    UnknownTag(String),
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 /// Structure the received JSON gets parsed to
 pub struct Message {
     /// Mode how the animation should be played
+    #[serde(default)]
     mode: String,
 
     /// A list of bytes of the animation
+    #[serde(default)]
     data: Vec<u8>,
+
+    /// Play a random animation of the library with this tag instead of sending one
+    #[serde(default)]
+    tag: Option<String>,
+
+    /// How the frames of the animation are played
+    #[serde(flatten)]
+    playback: Playback,
+
+    /// Change the running program instead of playing an animation
+    #[serde(default)]
+    control: Option<Control>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+/// Commands, that change the running program, e.g. `{"control": {"set_speed": 1.5}}`
+pub enum Control {
+    /// Set the global playback speed. It's clamped to a sensible range
+    SetSpeed(f32),
+
+    /// Set the color for base, blinks and grayscale animations. `null` keeps the colors of the animations
+    SetDefaultColor(Option<String>),
+
+    /// Replace the colors of the palette, keeping its mode
+    SetPalette(Vec<String>),
+
+    /// Read a palette file of the palette directory on the device and use it from now on
+    LoadPalette(PathBuf),
+
+    /// Read the palette file, that is used, again
+    ReloadPalette,
+}
+
+/// What a received message asks for
+enum Received {
+    /// An animation, that is to play
+    Animation(ProcessedMessage),
+
+    /// A command, that is to apply
+    Control(Control),
 }
 
 /// Once the `Message` is interpret, present it as a `ProcessedMessage`
@@ -43,6 +99,9 @@ pub struct ProcessedMessage {
 
     /// `PathBuf` to the animation
     path: PathBuf,
+
+    /// How the frames of the animation are played
+    playback: Playback,
 }
 
 #[derive(PartialEq, Debug)]
@@ -71,10 +130,13 @@ impl FromStr for PlayMode {
 }
 
 /// Open a TCP port and start receiving messages. Likely started as thread.
-pub fn start_recv_file_server(queue: Arc<Mutex<Vec<PathBuf>>>) {
+pub fn start_recv_file_server(queue: Arc<Mutex<Vec<QueuedAnimation>>>) {
     let binding = fallback_arguments();
     let args = ARGUMENTS.get().unwrap_or(&binding);
 
+    //Tagged animations are chosen by an own random stream, so messages don't change the ones of the player
+    init_thread_random(NETWORK_STREAM);
+
     let mut prev_recv_count: u8 = 0;
     let ip = SocketAddr::from(([0, 0, 0, 0], args.inject_port));
 
@@ -91,7 +153,11 @@ pub fn start_recv_file_server(queue: Arc<Mutex<Vec<PathBuf>>>) {
 
                 //Once a connection is established, start receiving
                 match receive_message(&mut connection, prev_recv_count) {
-                    Ok(p_message) => {
+                    Ok(Received::Control(control)) => {
+                        let answer = apply_control(control);
+                        send_answer(&mut connection, answer);
+                    }
+                    Ok(Received::Animation(p_message)) => {
 
                         //When message is received, check its play mode
                         match p_message.play_mode {
@@ -115,7 +181,7 @@ pub fn start_recv_file_server(queue: Arc<Mutex<Vec<PathBuf>>>) {
                                         send_answer(&mut connection, message);
 
                                         //Add to queue
-                                        vec.push(p_message.path);
+                                        vec.push(QueuedAnimation { path: p_message.path, playback: p_message.playback });
                                     }
                                     Err(e) => error!("Can't access mutex: {}", e.to_string())
                                 }
@@ -155,12 +221,13 @@ fn send_answer(connection: &mut TcpStream, answer: String) {
 /// * `prev_recv_count`: Indicates how many messages have been received before
 ///
 /// # Output
-/// A `Result<ProcessedMessage, NetworkError>` with
-/// * a `ProcessedMessage`, containing the `PlayMode` of the animation and a `PathBuf` where the animation is stored
+/// A `Result<Received, NetworkError>` with
+/// * a `Received`, containing either a `Control` command or a `ProcessedMessage` with the `PlayMode` of the
+///   animation and a `PathBuf` where the animation is stored
 /// * a `NetworkError` is thrown, when:
 ///     - `process_message()` fails
 ///     - the received message is empty
-fn receive_message(connection: &mut TcpStream, prev_recv_count: u8) -> Result<ProcessedMessage, NetworkError> {
+fn receive_message(connection: &mut TcpStream, prev_recv_count: u8) -> Result<Received, NetworkError> {
     let mut buffer: [u8; 4096] = [0; 4096];
     let mut data: Vec<u8> = Vec::new(); //u8 as in byte
 
@@ -185,7 +252,10 @@ fn receive_message(connection: &mut TcpStream, prev_recv_count: u8) -> Result<Pr
         match String::from_utf8(data) {
             Ok(json) => {
                 let message: Message = serde_json::from_str(&json)?;
-                return process_message(message, prev_recv_count);
+                if let Some(control) = message.control {
+                    return Ok(Received::Control(control));
+                }
+                return process_message(message, prev_recv_count).map(Received::Animation);
             }
             Err(e) => error!("Can't read received JSON object: {}", e.to_string()),
         }
@@ -205,8 +275,25 @@ fn receive_message(connection: &mut TcpStream, prev_recv_count: u8) -> Result<Pr
 /// * a `NetworkError` is thrown, when:
 ///     - `process_message()` fails
 ///     - the received message is empty
+///     - no animation has the wanted tag
 fn process_message(message: Message, prev_recv_count: u8) -> Result<ProcessedMessage, NetworkError> {
-    let name = format!("received_file_{}.gif", prev_recv_count);
+    //Without data, a tagged animation of the library is wanted
+    if let (true, Some(tag)) = (message.data.is_empty(), &message.tag) {
+        let binding = fallback_arguments();
+        let args = ARGUMENTS.get().unwrap_or(&binding);
+
+        let path = random_tagged_animation(Path::new(OTHER_PATH), &args.ignore_patterns, tag)?;
+        info!("Chose ({}) for tag \"{}\". Play mode is {}", path.display(), tag, message.mode);
+        return Ok(ProcessedMessage {
+            play_mode: PlayMode::from_str(&message.mode)?,
+            path,
+            playback: message.playback,
+        });
+    }
+
+    //Keep the extension in line with the received format, GIF is assumed when it's unknown
+    let extension = AnimationFormat::detect(&message.data).map(|f| f.extension()).unwrap_or("gif");
+    let name = format!("received_file_{}.{}", prev_recv_count, extension);
     let dir = temp_dir().join(name);
 
     return match std::fs::write(dir.clone(), message.data) {
@@ -216,12 +303,92 @@ fn process_message(message: Message, prev_recv_count: u8) -> Result<ProcessedMes
             Ok(ProcessedMessage {
                 play_mode: PlayMode::from_str(&*message.mode)?,
                 path: dir,
+                playback: message.playback,
             })
         }
         Err(e) => Err(NetworkError::from(e))
     };
 }
 
+/// Apply a received control command
+///
+/// # Input
+/// `control`: The `Control` command that is to apply
+///
+/// # Output
+/// The answer for the sender
+fn apply_control(control: Control) -> String {
+    match control {
+        Control::SetSpeed(speed) => format!("Set playback speed to {}", set_playback_speed(speed)),
+        Control::SetDefaultColor(None) => {
+            set_default_color(None);
+            "Keep colors of the animations".to_owned()
+        }
+        Control::SetDefaultColor(Some(color)) => match Color::from_str(&color) {
+            Ok(color) => {
+                set_default_color(Some(color));
+                format!("Set default color to #{}", color)
+            }
+            Err(e) => format!("Can't set default color: {}", e),
+        },
+        Control::SetPalette(colors) => palette_answer(set_palette_colors(&colors)),
+        Control::LoadPalette(path) => palette_answer(resolve_palette_path(Path::new(PALETTE_PATH), &path).and_then(|path| load_color_palette(&path))),
+        Control::ReloadPalette => palette_answer(reload_color_palette()),
+    }
+}
+
+/// Resolve the path of a palette, that a client wants to load, within the palette directory. This way
+/// clients can't read any other file of the device
+///
+/// # Input
+/// * `dir`: The directory palettes can be loaded from
+/// * `path`: The path of the palette, relative to the directory
+///
+/// # Output
+/// A `Result<PathBuf, ColorError>` with the canonical path of the palette, that fails when the palette
+/// doesn't exist or is outside of the directory
+fn resolve_palette_path(dir: &Path, path: &Path) -> Result<PathBuf, ColorError> {
+    let dir = dir.canonicalize()?;
+    let resolved = dir.join(path).canonicalize()?;
+    if !resolved.starts_with(&dir) {
+        return Err(ColorError::Palette(format!("({}) is outside of the palette directory", path.display())));
+    }
+
+    Ok(resolved)
+}
+
+/// Build the answer for a changed palette
+///
+/// # Input
+/// `result`: The count of colors of the new palette, or why the palette wasn't changed
+fn palette_answer(result: Result<usize, ColorError>) -> String {
+    match result {
+        Ok(count) => format!("Palette has now {} color/colors", count),
+        Err(e) => {
+            warn!("Can't change palette: {}", e);
+            format!("Can't change palette: {}", e)
+        }
+    }
+}
+
+/// Choose a random animation with a tag from the library
+///
+/// # Input
+/// * `root`: The directory of the library
+/// * `ignore_patterns`: Patterns of files and folders, that are to ignore
+/// * `tag`: The tag the animation needs to have
+///
+/// # Output
+/// A `Result<PathBuf, NetworkError>` with the path to the animation, that fails when the library can't
+/// be read or there's no animation with that tag
+fn random_tagged_animation(root: &Path, ignore_patterns: &[String], tag: &str) -> Result<PathBuf, NetworkError> {
+    let library = Library::scan(root, ignore_patterns)?;
+    match with_rng(|rng| library.random(Some(tag), rng).map(|entry| entry.path.clone())) {
+        Some(path) => Ok(path),
+        None => Err(NetworkError::UnknownTag(tag.to_owned())),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -234,6 +401,9 @@ mod tests {
         let message = Message {
             data: vec![0, 1, 2, 3, 4],
             mode: "queued".to_string(),
+            tag: None,
+            playback: Playback::default(),
+            control: None,
         };
         let prev_recv_count = 0;
 
@@ -245,4 +415,51 @@ mod tests {
         let file_contents = fs::read(expected_path).unwrap();
         assert_eq!(file_contents, vec![0, 1, 2, 3, 4]);
     }
+
+    #[test]
+    fn test_parse_control() {
+        let message: Message = serde_json::from_str(r#"{"control": {"set_speed": 1.5}}"#).unwrap();
+        assert_eq!(message.control, Some(Control::SetSpeed(1.5)));
+
+        let message: Message = serde_json::from_str(r#"{"control": {"set_default_color": null}}"#).unwrap();
+        assert_eq!(message.control, Some(Control::SetDefaultColor(None)));
+
+        let message: Message = serde_json::from_str(r#"{"control": {"set_palette": ["FF0000", "hotpink"]}}"#).unwrap();
+        assert_eq!(message.control, Some(Control::SetPalette(vec!["FF0000".to_owned(), "hotpink".to_owned()])));
+
+        let message: Message = serde_json::from_str(r#"{"control": "reload_palette"}"#).unwrap();
+        assert_eq!(message.control, Some(Control::ReloadPalette));
+
+        let message: Message = serde_json::from_str(r#"{"mode": "queued", "data": [], "speed": 2.0}"#).unwrap();
+        assert_eq!(message.control, None);
+        assert_eq!(message.playback.speed, Some(2.0));
+    }
+
+    #[test]
+    fn test_resolve_palette_path() {
+        let temp_dir = TempDir::new("test_resolve_palette_path").unwrap();
+        let dir = temp_dir.path().join("palettes");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("halloween.gpl"), "").unwrap();
+        fs::write(temp_dir.path().join("secret.txt"), "").unwrap();
+
+        let expected = dir.join("halloween.gpl").canonicalize().unwrap();
+        assert_eq!(resolve_palette_path(&dir, Path::new("halloween.gpl")).unwrap(), expected);
+        assert_eq!(resolve_palette_path(&dir, &dir.join("halloween.gpl")).unwrap(), expected);
+        assert!(resolve_palette_path(&dir, Path::new("../secret.txt")).is_err());
+        assert!(resolve_palette_path(&dir, &temp_dir.path().join("secret.txt")).is_err());
+        assert!(resolve_palette_path(&dir, Path::new("missing.gpl")).is_err());
+    }
+
+    #[test]
+    fn test_random_tagged_animation() {
+        let temp_dir = TempDir::new("test_random_tagged_animation").unwrap();
+        fs::create_dir(temp_dir.path().join("happy")).unwrap();
+        fs::write(temp_dir.path().join("happy/smile.gif"), "").unwrap();
+        fs::write(temp_dir.path().join("neutral.gif"), "").unwrap();
+
+        let path = random_tagged_animation(temp_dir.path(), &[], "happy").unwrap();
+        assert_eq!(path, temp_dir.path().join("happy/smile.gif"));
+        assert!(matches!(random_tagged_animation(temp_dir.path(), &[], "sad"), Err(NetworkError::UnknownTag(_))));
+    }
 }
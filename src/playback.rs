@@ -0,0 +1,136 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::gif::{Animation, delay_from_millis};
+use crate::gif::cycle::ColorCycle;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+#[serde(default)]
+/// Modifiers, how the frames of an animation are played. Can be part of the metadata, playlist entries
+/// and network messages
+pub struct Playback {
+    ///Play the frames backwards
+    pub reverse: bool,
+
+    ///Play the frames forward and then backwards, without repeating the first and last frame
+    pub ping_pong: bool,
+
+    ///Only play the frames from the first to the second index, both included
+    pub range: Option<(usize, usize)>,
+
+    ///Milliseconds the last frame is shown longer
+    pub hold_last: u32,
+
+    ///Factor for the frame delays, 2.0 plays twice as fast. Multiplies with the speed of the metadata and
+    ///the global playback speed instead of replacing them
+    pub speed: Option<f32>,
+
+    ///Ranges of the palette, whose colors rotate over time. Replace the ones of the metadata on their own
+    pub color_cycles: Vec<ColorCycle>,
+}
+
+impl Playback {
+    /// Check if any modifier of the frames is set. The speed and the color cycles aren't one of them
+    pub fn is_set(&self) -> bool {
+        Playback { speed: None, color_cycles: Vec::new(), ..self.clone() } != Playback::default()
+    }
+
+    /// Get the speed factor, 1.0 if it isn't set
+    pub fn speed(&self) -> f32 {
+        self.speed.unwrap_or(1.0)
+    }
+
+    /// Apply all modifiers to an animation. The range is applied first, then the direction
+    ///
+    /// # Input
+    /// The `Animation` that is to modify
+    pub fn apply(&self, anim: &mut Animation) {
+        if let Some((start, end)) = self.range {
+            let end = end.min(anim.frames.len().saturating_sub(1));
+            if start <= end {
+                anim.frames = anim.frames.drain(start..=end).collect();
+            }
+        }
+
+        if self.reverse {
+            anim.frames.reverse();
+        }
+
+        if self.ping_pong && anim.frames.len() > 2 {
+            let back: Vec<_> = anim.frames[1..anim.frames.len() - 1].iter().rev().cloned().collect();
+            anim.frames.extend(back);
+        }
+
+        if let Some(last) = anim.frames.last_mut() {
+            last.delay = last.delay.saturating_add(delay_from_millis(self.hold_last));
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+/// An animation waiting in the queue, together with how it's to play
+pub struct QueuedAnimation {
+    /// The path to the animation
+    pub path: PathBuf,
+
+    /// The modifiers, that replace the ones of the metadata, if set
+    pub playback: Playback,
+}
+
+impl From<PathBuf> for QueuedAnimation {
+    fn from(path: PathBuf) -> Self {
+        QueuedAnimation { path, playback: Playback::default() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gif::fixtures::blank_animation;
+
+    use super::*;
+
+    fn delays(anim: &Animation) -> Vec<u16> {
+        anim.frames.iter().map(|frame| frame.delay).collect()
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut anim = blank_animation(&[0, 1, 2, 3]);
+        Playback { reverse: true, ..Default::default() }.apply(&mut anim);
+        assert_eq!(delays(&anim), vec![3, 2, 1, 0]);
+
+        let mut anim = blank_animation(&[0, 1, 2, 3]);
+        Playback { ping_pong: true, ..Default::default() }.apply(&mut anim);
+        assert_eq!(delays(&anim), vec![0, 1, 2, 3, 2, 1]);
+
+        let mut anim = blank_animation(&[0, 1, 2, 3, 4, 5]);
+        Playback { range: Some((1, 3)), reverse: true, hold_last: 100, ..Default::default() }.apply(&mut anim);
+        assert_eq!(delays(&anim), vec![3, 2, 11]);
+
+        let mut anim = blank_animation(&[0, 1, 2]);
+        Playback { range: Some((1, 99)), ..Default::default() }.apply(&mut anim);
+        assert_eq!(delays(&anim), vec![1, 2]);
+
+        let mut anim = blank_animation(&[0, 1, 2]);
+        Playback { range: Some((5, 99)), ..Default::default() }.apply(&mut anim);
+        assert_eq!(delays(&anim), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let playback: Playback = serde_json::from_str(r#"{"ping_pong": true, "range": [2, 5]}"#).unwrap();
+        assert_eq!(playback, Playback { ping_pong: true, range: Some((2, 5)), ..Default::default() });
+        assert!(playback.is_set());
+        assert!(!Playback::default().is_set());
+
+        let playback: Playback = serde_json::from_str(r#"{"speed": 2.0}"#).unwrap();
+        assert!(!playback.is_set());
+        assert_eq!(playback.speed(), 2.0);
+        assert_eq!(Playback::default().speed(), 1.0);
+
+        let playback: Playback = serde_json::from_str(r#"{"color_cycles": [{"range": [16, 31], "speed": 8}]}"#).unwrap();
+        assert!(!playback.is_set());
+        assert_eq!(playback.color_cycles, vec![ColorCycle { range: (16, 31), speed: 8.0 }]);
+    }
+}
@@ -0,0 +1,212 @@
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::arguments::{ARGUMENTS, fallback_arguments};
+use crate::clock::Clock;
+use crate::color::Color;
+use crate::color_mapping::ColorMapper;
+use crate::gif::{Animation, Frame, Pixel};
+use crate::renderer::play_frames;
+use crate::renderer::tasbot_eyes::SCREEN_WIDTH;
+
+///How often the colors are updated, while nothing else is shown
+pub const REFRESH_INTERVAL: Duration = Duration::from_millis(50);
+
+///How far the hue of the right eye is ahead of the left one
+const EYE_OFFSET: f32 = 180.0;
+
+#[derive(clap::ValueEnum, Copy, Clone, PartialEq, Debug)]
+/// How the hue of the rainbow is spread over the display
+pub enum RainbowMode {
+    /// The whole display has the same hue
+    Single,
+
+    /// The hue changes over the columns, so the rainbow sweeps across the display
+    Sweep,
+
+    /// Both eyes have opposite hues
+    PerEye,
+}
+
+impl Display for RainbowMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            RainbowMode::Single => "single",
+            RainbowMode::Sweep => "sweep",
+            RainbowMode::PerEye => "per-eye",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+/// Colors grayscale animations by a hue, that moves over time
+pub struct Rainbow {
+    /// How the hue is spread over the display
+    pub mode: RainbowMode,
+
+    /// How many degrees the hue moves per second
+    pub speed: f32,
+
+    /// The saturation of the colors between 0.0 and 1.0
+    pub saturation: f32,
+}
+
+impl Rainbow {
+    /// Get the rainbow of the arguments
+    ///
+    /// # Output
+    /// The `Rainbow` or `None`, if the rainbow mode isn't used
+    pub fn from_arguments() -> Option<Rainbow> {
+        let binding = fallback_arguments();
+        let args = ARGUMENTS.get().unwrap_or(&binding);
+
+        args.rainbow.map(|mode| Rainbow {
+            mode,
+            speed: args.rainbow_speed,
+            saturation: args.rainbow_saturation.clamp(0.0, 1.0),
+        })
+    }
+
+    /// Get the color of a column at a point in time
+    ///
+    /// # Input
+    /// * `time`: The time of the clock, the renderer is using
+    /// * `x`: The column of the display
+    ///
+    /// # Output
+    /// The `Color` of the column
+    pub fn color(&self, time: Duration, x: usize) -> Color {
+        let offset = match self.mode {
+            RainbowMode::Single => 0.0,
+            RainbowMode::Sweep => x as f32 * 360.0 / SCREEN_WIDTH as f32,
+            RainbowMode::PerEye if x < SCREEN_WIDTH / 2 => 0.0,
+            RainbowMode::PerEye => EYE_OFFSET,
+        };
+
+        //Keep the time small, so the hue doesn't lose precision in long runs
+        let hue = (time.as_secs_f64() * self.speed as f64).rem_euclid(360.0) as f32;
+        Color::from_hsv(hue, self.saturation, 1.0).rotate_hue(offset)
+    }
+}
+
+/// Shows a frame on a renderer, with a function that gets the color of a pixel by its column
+pub type ShowFrame<'a> = dyn FnMut(&Frame, &dyn Fn(usize, &Pixel) -> Color) + 'a;
+
+/// Plays animations in the colors of a `Rainbow` for a renderer. The last frame is remembered, so the renderer
+/// can show it again in the current colors, while nothing else is shown
+pub struct RainbowPlayer {
+    /// The rainbow the animations are colored with
+    rainbow: Rainbow,
+
+    /// How the pixels are mapped onto the colors of the rainbow
+    mapper: ColorMapper,
+
+    /// The animation and index of the last frame shown with the rainbow
+    last_frame: Option<(Arc<Animation>, usize)>,
+}
+
+impl RainbowPlayer {
+    pub fn new(rainbow: Rainbow, mapper: ColorMapper) -> Self {
+        RainbowPlayer { rainbow, mapper, last_frame: None }
+    }
+
+    /// Get a player for the rainbow and color mapping of the arguments
+    ///
+    /// # Output
+    /// The `RainbowPlayer` or `None`, if the rainbow mode isn't used
+    pub fn from_arguments() -> Option<RainbowPlayer> {
+        Rainbow::from_arguments().map(|rainbow| RainbowPlayer::new(rainbow, ColorMapper::from_arguments()))
+    }
+
+    /// Play an animation in the colors of the rainbow. Animations without anything to colorize are not played
+    ///
+    /// # Input
+    /// * `anim`: The `Animation` that should be played
+    /// * `clock`: The `Clock` of the renderer
+    /// * `show`: Shows a frame on the renderer, with a function that gets the color of a pixel by its column
+    ///
+    /// # Output
+    /// If the animation was played
+    pub fn play(&mut self, anim: &Arc<Animation>, clock: &dyn Clock, show: &mut ShowFrame) -> bool {
+        if !self.mapper.colorizes(anim) {
+            return false;
+        }
+
+        play_frames(&anim.frames, clock, |index, frame| {
+            show_frame(&self.rainbow, frame, clock.now(), &self.mapper, show);
+            self.last_frame = Some((anim.clone(), index));
+        });
+        true
+    }
+
+    /// Show the last frame again in the current colors of the rainbow, if there is one
+    ///
+    /// # Input
+    /// * `clock`: The `Clock` of the renderer
+    /// * `show`: Shows a frame on the renderer, like for `play()`
+    pub fn refresh(&self, clock: &dyn Clock, show: &mut ShowFrame) {
+        if let Some((anim, index)) = &self.last_frame {
+            show_frame(&self.rainbow, &anim.frames[*index], clock.now(), &self.mapper, show);
+        }
+    }
+
+    /// Forget the last frame, as something else is shown now
+    pub fn forget(&mut self) {
+        self.last_frame = None;
+    }
+}
+
+/// Show a frame in the colors of the rainbow at a point in time
+fn show_frame(rainbow: &Rainbow, frame: &Frame, now: Duration, mapper: &ColorMapper, show: &mut ShowFrame) {
+    show(frame, &|x, pixel| mapper.colorize(frame, pixel, &rainbow.color(now, x)));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::VirtualClock;
+    use crate::color::{CYAN, RED, WHITE};
+    use crate::color_mapping::ColorMapping;
+    use crate::gif::fixtures::text_animation;
+    use crate::gif::text::read_text;
+
+    use super::*;
+
+    #[test]
+    fn test_rainbow_player() {
+        let anim = Arc::new(read_text(&text_animation(&[1000])).unwrap());
+        let clock = VirtualClock::new();
+        let rainbow = Rainbow { mode: RainbowMode::Single, speed: 90.0, saturation: 1.0 };
+        let mut player = RainbowPlayer::new(rainbow, ColorMapper::new(ColorMapping::Flat, Vec::new()));
+        let mut shown: Vec<Color> = Vec::new();
+
+        assert!(player.play(&anim, &clock, &mut |frame, colorize| shown.push(colorize(0, &frame.pixels[0][0]))));
+        clock.advance(Duration::from_secs(1));
+        player.refresh(&clock, &mut |frame, colorize| shown.push(colorize(0, &frame.pixels[0][0])));
+        player.forget();
+        player.refresh(&clock, &mut |frame, colorize| shown.push(colorize(0, &frame.pixels[0][0])));
+
+        //The frame is shown again a second after it ended, when the hue moved on by 180 degrees
+        assert_eq!(shown, vec![RED, CYAN]);
+    }
+
+    #[test]
+    fn test_color() {
+        let rainbow = Rainbow { mode: RainbowMode::Single, speed: 90.0, saturation: 1.0 };
+        assert_eq!(rainbow.color(Duration::ZERO, 0), RED);
+        assert_eq!(rainbow.color(Duration::from_secs(2), 27), CYAN);
+        assert_eq!(rainbow.color(Duration::from_secs(4), 5), RED);
+
+        let sweep = Rainbow { mode: RainbowMode::Sweep, ..rainbow };
+        assert_eq!(sweep.color(Duration::ZERO, 0), RED);
+        assert_eq!(sweep.color(Duration::ZERO, 14), CYAN);
+
+        let per_eye = Rainbow { mode: RainbowMode::PerEye, ..rainbow };
+        assert_eq!(per_eye.color(Duration::ZERO, 13), RED);
+        assert_eq!(per_eye.color(Duration::ZERO, 14), CYAN);
+
+        let pale = Rainbow { saturation: 0.0, ..rainbow };
+        assert_eq!(pale.color(Duration::from_secs(1), 0), WHITE);
+    }
+}
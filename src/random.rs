@@ -0,0 +1,120 @@
+use std::cell::RefCell;
+
+use log::{info, warn};
+use once_cell::sync::OnceCell;
+use rand::{Rng, SeedableRng, thread_rng};
+use rand::rngs::StdRng;
+
+///The stream of random numbers of the thread, that plays the animations
+pub const MAIN_STREAM: u64 = 0;
+
+///The stream of random numbers of the network thread
+pub const NETWORK_STREAM: u64 = 1;
+
+///The seed of the run. Every thread derives its own random number generator from it
+static SEED: OnceCell<u64> = OnceCell::new();
+
+thread_local! {
+    ///The random number generator of this thread. Threads don't share one, so they can't change the
+    ///sequence of each other, no matter when they run
+    static RNG: RefCell<Option<StdRng>> = const { RefCell::new(None) };
+}
+
+/// Initialize the random number generator of the current thread as the main stream. Without a seed, a
+/// random one is chosen and logged, so the run can be reproduced later with `--seed`. Only the first seed
+/// is the one of the run, a different seed of a later call only applies to the current thread
+///
+/// # Input
+/// `seed`: The seed that is to use, if any
+pub fn init_random(seed: Option<u64>) {
+    let seed = seed.unwrap_or_else(|| thread_rng().gen());
+    info!("Using {} as random seed", seed);
+
+    //Only the first seed is the one of the run, which other threads derive their streams from
+    let run_seed = *SEED.get_or_init(|| seed);
+    if run_seed != seed {
+        warn!("The random seed of the run is already {}, other threads keep using it instead of {}", run_seed, seed);
+    }
+    seed_thread(seed, MAIN_STREAM);
+}
+
+/// Give the current thread its own random number generator, that is derived from the seed of the run.
+/// Without a seed, it's seeded randomly
+///
+/// # Input
+/// `stream`: Which stream of the seed to use, e.g. `NETWORK_STREAM`
+pub fn init_thread_random(stream: u64) {
+    match SEED.get() {
+        Some(seed) => seed_thread(*seed, stream),
+        None => RNG.with(|rng| *rng.borrow_mut() = Some(StdRng::from_entropy())),
+    }
+}
+
+/// Seed the random number generator of the current thread
+///
+/// # Input
+/// * `seed`: The seed of the run
+/// * `stream`: The stream of this thread, so threads with the same seed get different sequences
+fn seed_thread(seed: u64, stream: u64) {
+    let seed = seed ^ stream.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    RNG.with(|rng| *rng.borrow_mut() = Some(StdRng::seed_from_u64(seed)));
+}
+
+/// Run something with the random number generator of the current thread. If it wasn't initialized yet,
+/// it's derived from the seed of the run as the main stream or seeded randomly
+///
+/// # Input
+/// `f`: The function that needs the random number generator
+///
+/// # Output
+/// Whatever the function returns
+pub fn with_rng<T, F: FnOnce(&mut StdRng) -> T>(f: F) -> T {
+    if RNG.with(|rng| rng.borrow().is_none()) {
+        init_thread_random(MAIN_STREAM);
+    }
+
+    RNG.with(|rng| f(rng.borrow_mut().as_mut().unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sequence() -> Vec<u16> {
+        (0..10).map(|_| with_rng(|rng| rng.gen())).collect()
+    }
+
+    #[test]
+    fn test_with_rng() {
+        init_random(Some(42));
+        let a: u32 = with_rng(|rng| rng.gen());
+        let b: u32 = with_rng(|rng| rng.gen());
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        init_random(Some(1337));
+        let a = sequence();
+        init_random(Some(1337));
+        let b = sequence();
+        assert_eq!(a, b);
+
+        init_random(Some(1338));
+        assert_ne!(sequence(), a);
+    }
+
+    #[test]
+    fn test_streams_differ() {
+        seed_thread(1337, MAIN_STREAM);
+        let main = sequence();
+        seed_thread(1337, NETWORK_STREAM);
+        let network = sequence();
+        assert_ne!(main, network);
+
+        //Other threads don't change the sequence of this one
+        seed_thread(1337, MAIN_STREAM);
+        std::thread::spawn(sequence).join().unwrap();
+        assert_eq!(sequence(), main);
+    }
+}
@@ -1,10 +1,20 @@
+use std::collections::VecDeque;
 use std::path::PathBuf;
-use std::time;
+use std::sync::{Arc, Mutex};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
 
 use log::{info, warn};
+use once_cell::sync::Lazy;
 
+use crate::arguments::{ARGUMENTS, fallback_arguments};
+use crate::clock::Clock;
 use crate::color::Color;
-use crate::gif::{Animation, Frame, read_animation};
+use crate::file_operations::{Metadata, modified, read_metadata, Transition};
+use crate::gif::{Animation, Frame, GifError, read_animation, split_tag};
+use crate::gif::cycle::apply_color_cycles;
+use crate::playback::Playback;
+use crate::settings::{clamp_playback_speed, playback_speed};
 
 ///Renderer, that animates no animation at all (for debugging or testing)
 pub mod silent;
@@ -21,71 +31,564 @@ pub mod tasbot_eyes;
 /// This is more pseudocode then anything else! This is not tested and might not work!
 pub mod led_matrix;
 
+///Animations converted to ready-to-copy LED buffers
+pub mod compiled;
+
+/// Frame delays up to this (in 10 ms) count as tiny. Browsers don't play those as they are
+pub const TINY_DELAY: u16 = 1;
+
+/// The delay (in 10 ms) browsers use instead of a tiny one
+pub const BROWSER_DELAY: u16 = 10;
+
+/// How many prepared animations are kept, so base, blinks and the last other animations aren't read again
+pub const PREPARED_SIZE: usize = 8;
+
+#[derive(clap::ValueEnum, Copy, Clone, PartialEq, Debug)]
+/// How zero and tiny frame delays are handled
+pub enum DelayPolicy {
+    /// Delays of 0 or 10 ms become 100 ms, like browsers do
+    Browser,
+
+    /// Delays below the minimum frame delay are raised to it
+    Clamp,
+
+    /// Delays are used as they are
+    Keep,
+}
+
 ///The universial interface for all renderer
 pub trait Renderer {
     ///Play the animation as it is
-    fn play(&mut self, anim: Animation);
+    fn play(&mut self, anim: Arc<Animation>);
 
     ///Play the animation, but overwrite the color of the animation
-    fn play_colored(&mut self, anim: Animation, color: &Color);
+    fn play_colored(&mut self, anim: Arc<Animation>, color: &Color);
 
     ///Clear the renderer medium
     fn clear(&mut self);
 
     ///Print the configuration of the renderer
     fn print_config(&self);
+
+    ///The clock the renderer waits with between frames
+    fn clock(&self) -> &dyn Clock;
+
+    ///How often the renderer wants to `refresh()` while waiting, if its colors move over time
+    fn refresh_interval(&self) -> Option<Duration> { None }
+
+    ///Show the last frame again, so colors that move over time keep moving
+    fn refresh(&mut self) {}
+
+    ///Play the animation in colors, that move over time, instead of an overwrite color. Renderers without
+    ///those don't play anything
+    ///
+    /// # Output
+    /// If the animation was played
+    fn play_rainbow(&mut self, _anim: &Arc<Animation>) -> bool { false }
 }
 
-/// Play an animation from a given path with a given color option
+/// Play an animation from a given path with a given color option. The metadata sidecar file of the
+/// animation is respected, so it might get played faster, repeated or in a fixed color
 ///
 /// # Input
 /// * `renderer`: The renderer that is to use to render the animation
 /// * `path`: The `PathBuf` to the animation
 /// * `color`: The `Option<Color>`, if the color should be overwritten and with which color
-pub fn play_animation_from_path<T: Renderer>(renderer: &mut T, path: PathBuf, color: Option<Color>) {
-    let anim = read_animation(&path);
-    match anim {
-        Ok(anim) => {
-            match color {
-                None => {
-                    info!("Attempt to play ({})", path.to_str().unwrap_or("Invalid path"));
-                    renderer.play(anim);
-                }
-                Some(color) => {
-                    info!("Attempt to play ({}) with (#{}) as color overwrite", path.to_str().unwrap_or("Invalid path"), color);
-                    renderer.play_colored(anim, &color);
-                }
+/// * `playback`: The `Playback` modifiers, that replace the ones of the metadata if any is set. Its speed
+///   is multiplied with the speed of the metadata and the global playback speed
+pub fn play_animation_from_path<T: Renderer>(renderer: &mut T, path: PathBuf, color: Option<Color>, playback: &Playback) {
+    let (anim, metadata) = match prepare_animation(&path, playback) {
+        Ok(prepared) => prepared,
+        Err(err) => {
+            warn!("Can't read ({}): {}", path.to_str().unwrap_or("Invalid path"), err);
+            return;
+        }
+    };
+
+    //A fixed color wins over everything, otherwise the animation might forbid an overwrite
+    let color = match metadata.color.as_deref().map(Color::from_str) {
+        Some(Ok(fixed)) => Some(fixed),
+        Some(Err(e)) => {
+            warn!("Invalid color in metadata of ({}): {}", path.to_str().unwrap_or("Invalid path"), e);
+            color.filter(|_| metadata.allow_color_overwrite)
+        }
+        None => color.filter(|_| metadata.allow_color_overwrite),
+    };
+
+    //The rainbow replaces the overwrite color, so it's only used where an overwrite is allowed
+    let rainbow_allowed = metadata.allow_color_overwrite && metadata.color.is_none();
+
+    for _ in 0..metadata.repetitions {
+        if rainbow_allowed && renderer.play_rainbow(&anim) {
+            info!("Played ({}) in the colors of the rainbow", path.to_str().unwrap_or("Invalid path"));
+            continue;
+        }
+
+        match color {
+            None => {
+                info!("Attempt to play ({})", path.to_str().unwrap_or("Invalid path"));
+                renderer.play(anim.clone());
+            }
+            Some(color) => {
+                info!("Attempt to play ({}) with (#{}) as color overwrite", path.to_str().unwrap_or("Invalid path"), color);
+                renderer.play_colored(anim.clone(), &color);
             }
         }
+    }
 
-        Err(err) => {
-            warn!("Can't read ({}): {}", path.to_str().unwrap_or("Invalid path"), err.to_string());
+    if metadata.transition == Transition::Clear {
+        renderer.clear();
+    }
+}
+
+/// An animation, that is read and modified for playing, together with what it was prepared from
+struct PreparedAnimation {
+    /// The path of the animation
+    path: PathBuf,
+
+    /// When the file of the animation and its metadata sidecar file were modified
+    modified: [Option<SystemTime>; 2],
+
+    /// The `Playback` modifiers the animation was prepared with
+    playback: Playback,
+
+    /// The factor the frame delays were scaled with
+    speed: f32,
+
+    /// The metadata of the animation
+    metadata: Metadata,
+
+    /// The prepared animation
+    anim: Arc<Animation>,
+}
+
+#[derive(Default)]
+/// The last prepared animations, the most recently played first
+struct PreparedCache {
+    entries: VecDeque<PreparedAnimation>,
+}
+
+impl PreparedCache {
+    /// Get a prepared animation, if it's cached and still up to date
+    ///
+    /// # Input
+    /// * `path`: The `PathBuf` to the animation
+    /// * `modified`: When the file of the animation and its metadata sidecar file were modified
+    /// * `playback`: The `Playback` modifiers it's played with
+    ///
+    /// # Output
+    /// The prepared `Animation` and its `Metadata`, if cached
+    fn get(&mut self, path: &PathBuf, modified: &[Option<SystemTime>; 2], playback: &Playback) -> Option<(Arc<Animation>, Metadata)> {
+        //The global playback speed might have changed in the meantime
+        let index = self.entries.iter().position(|cached| &cached.path == path && &cached.modified == modified
+            && &cached.playback == playback && cached.speed == combined_speed(&cached.metadata, playback))?;
+
+        let cached = self.entries.remove(index)?;
+        let result = (cached.anim.clone(), cached.metadata.clone());
+        self.entries.push_front(cached);
+        Some(result)
+    }
+
+    /// Cache a prepared animation instead of an older one of the same path. The least recently used ones
+    /// are dropped, if there are too many
+    fn insert(&mut self, prepared: PreparedAnimation) {
+        self.entries.retain(|cached| cached.path != prepared.path);
+        self.entries.push_front(prepared);
+        self.entries.truncate(PREPARED_SIZE);
+    }
+}
+
+/// The prepared animations. Playing a file again shares the same frames, so renderers can recognize it
+/// and don't have to convert it again
+static PREPARED: Lazy<Mutex<PreparedCache>> = Lazy::new(|| Mutex::new(PreparedCache::default()));
+
+/// Read an animation and apply its metadata and playback modifiers. The result is reused, as long as the
+/// file, its metadata and the modifiers didn't change
+///
+/// # Input
+/// * `path`: The `PathBuf` to the animation
+/// * `playback`: The `Playback` modifiers, see `play_animation_from_path()`
+///
+/// # Output
+/// The prepared `Animation` and its `Metadata`
+fn prepare_animation(path: &PathBuf, playback: &Playback) -> Result<(Arc<Animation>, Metadata), GifError> {
+    let modified = [modified(&split_tag(path).0), modified(&path.with_extension("json"))];
+
+    //The lock isn't held while reading, so other threads don't wait for the file
+    if let Some(cached) = PREPARED.lock().unwrap_or_else(|e| e.into_inner()).get(path, &modified, playback) {
+        return Ok(cached);
+    }
+
+    let mut anim = read_animation(path)?;
+    let metadata = read_metadata(path);
+
+    //The animation can overwrite the minimum frame delay of the arguments
+    let binding = fallback_arguments();
+    let args = ARGUMENTS.get().unwrap_or(&binding);
+    let (policy, min_delay) = match metadata.min_frame_delay {
+        None => (args.frame_delay_policy, args.min_frame_delay),
+        Some(min_delay) => (DelayPolicy::Clamp, min_delay),
+    };
+
+    //Tiny delays of the file are replaced like browsers do before the speed scales them like any other
+    //delay, so faster playback can go below 100 ms. A minimum delay applies to the scaled delays instead,
+    //so speeding up can't undercut it
+    if policy == DelayPolicy::Browser {
+        apply_delay_policy(&mut anim, policy, min_delay);
+    }
+    let speed = combined_speed(&metadata, playback);
+    apply_speed(&mut anim, speed);
+    if policy != DelayPolicy::Browser {
+        apply_delay_policy(&mut anim, policy, min_delay);
+    }
+
+    //The other modifiers replace the ones of the metadata
+    let frames_playback = if playback.is_set() { playback } else { &metadata.playback };
+    frames_playback.apply(&mut anim);
+
+    //Cycled last, so the colors move in real time, no matter how the frames are played
+    let color_cycles = if playback.color_cycles.is_empty() { &metadata.playback.color_cycles } else { &playback.color_cycles };
+    apply_color_cycles(&mut anim, color_cycles);
+
+    let anim = Arc::new(anim);
+    let prepared = PreparedAnimation { path: path.to_path_buf(), modified, playback: playback.clone(), speed, metadata: metadata.clone(), anim: anim.clone() };
+    PREPARED.lock().unwrap_or_else(|e| e.into_inner()).insert(prepared);
+    Ok((anim, metadata))
+}
+
+/// Get the speed an animation is played with. The speeds of the metadata, the playback modifiers and the
+/// global playback speed multiply, the product is clamped like the global playback speed
+///
+/// # Input
+/// * `metadata`: The `Metadata` of the animation
+/// * `playback`: The `Playback` modifiers it's played with
+///
+/// # Output
+/// The factor for the frame delays
+fn combined_speed(metadata: &Metadata, playback: &Playback) -> f32 {
+    clamp_playback_speed(metadata.playback.speed() * playback.speed() * playback_speed())
+}
+
+/// Change zero and tiny frame delays of an animation
+///
+/// # Input
+/// * `anim`: The `Animation` whose delays are to change
+/// * `policy`: The `DelayPolicy` to use
+/// * `min_delay`: The minimum delay in milliseconds for `DelayPolicy::Clamp`
+pub fn apply_delay_policy(anim: &mut Animation, policy: DelayPolicy, min_delay: u32) {
+    let min_delay = (min_delay.div_ceil(10)).min(u16::MAX as u32) as u16;
+
+    for frame in anim.frames.iter_mut() {
+        match policy {
+            DelayPolicy::Browser if frame.delay <= TINY_DELAY => frame.delay = BROWSER_DELAY,
+            DelayPolicy::Clamp => frame.delay = frame.delay.max(min_delay),
+            _ => {}
         }
     }
 }
 
-/// Sleep between the `Frame`s, based on the delay, thats was encoded with the frame
+/// Change the delays of all frames of an animation by a speed factor
 ///
 /// # Input
-/// The current `Frame` with its delay
-pub fn sleep_frame_delay(frame: &Frame) {
-    let ms = time::Duration::from_millis((frame.delay * 10) as u64);
-    info!("Sleeping for delay for {} ms", ms.as_millis());
-    std::thread::sleep(ms);
+/// * `anim`: The `Animation` that is to speed up or slow down
+/// * `speed`: The factor, 2.0 plays twice as fast. Values of 0 and below are ignored
+fn apply_speed(anim: &mut Animation, speed: f32) {
+    if speed <= 0.0 || speed == 1.0 {
+        return;
+    }
+
+    for frame in anim.frames.iter_mut() {
+        frame.delay = ((frame.delay as f32) / speed).round().min(u16::MAX as f32) as u16;
+    }
+}
+
+/// Show all frames of an animation on time. Every frame has an absolute deadline, counted from the start
+/// of the animation, so the time the renderer needs to show a frame doesn't add up
+///
+/// # Input
+/// * `frames`: All `Frame`s that are to show
+/// * `clock`: The `Clock` that is to wait with
+/// * `show`: Shows a single frame on the renderer, gets the index of the frame as well
+///
+/// # Output
+/// A `PlaybackReport` with the frames that were late or skipped
+pub fn play_frames<F: FnMut(usize, &Frame)>(frames: &[Frame], clock: &dyn Clock, mut show: F) -> PlaybackReport {
+    let binding = fallback_arguments();
+    let args = ARGUMENTS.get().unwrap_or(&binding);
+
+    let mut scheduler = FrameScheduler::new(clock, args.skip_late_frames);
+    for (index, frame) in frames.iter().enumerate() {
+        //The last frame is always shown, so an animation never ends in the wrong state
+        if scheduler.should_skip(frame, index + 1 == frames.len()) {
+            continue;
+        }
+
+        show(index, frame);
+        scheduler.wait(frame);
+    }
+
+    scheduler.report(frames.len())
+}
+
+#[derive(PartialEq, Debug)]
+/// How well an animation was played on time
+pub struct PlaybackReport {
+    /// Count of frames, that were shown after their delay was already over
+    pub late_frames: usize,
+
+    /// Count of frames, that weren't shown at all, because the renderer was behind
+    pub skipped_frames: usize,
+
+    /// The most a frame was late
+    pub max_lateness: Duration,
+}
+
+/// Waits for the absolute deadlines of the frames of an animation
+struct FrameScheduler<'a> {
+    /// The `Clock` that is waited with
+    clock: &'a dyn Clock,
+
+    /// The time the current frame ends and the next one is due, counted by the clock
+    deadline: Duration,
+
+    /// Indicates, if frames are skipped when the renderer falls behind
+    skip_late_frames: bool,
+
+    /// Count of frames, that were shown after their delay was already over
+    late_frames: usize,
+
+    /// Count of frames, that were skipped
+    skipped_frames: usize,
+
+    /// The most a frame was late
+    max_lateness: Duration,
+}
+
+impl<'a> FrameScheduler<'a> {
+    /// Start scheduling an animation, which starts now
+    fn new(clock: &'a dyn Clock, skip_late_frames: bool) -> Self {
+        FrameScheduler {
+            clock,
+            deadline: clock.now(),
+            skip_late_frames,
+            late_frames: 0,
+            skipped_frames: 0,
+            max_lateness: Duration::ZERO,
+        }
+    }
+
+    /// Check if a frame is to skip, because its entire delay is already over
+    fn should_skip(&mut self, frame: &Frame, is_last: bool) -> bool {
+        let end = self.deadline + frame_duration(frame);
+        if !self.skip_late_frames || is_last || self.clock.now() < end {
+            return false;
+        }
+
+        info!("Skip frame, as it's {} ms behind", (self.clock.now() - self.deadline).as_millis());
+        self.deadline = end;
+        self.skipped_frames += 1;
+        true
+    }
+
+    /// Wait until the shown frame is over. If that's already the case, the frame is counted as late
+    fn wait(&mut self, frame: &Frame) {
+        self.deadline += frame_duration(frame);
+        let now = self.clock.now();
+
+        if now > self.deadline {
+            let lateness = now - self.deadline;
+            info!("Frame is {} ms late", lateness.as_millis());
+            self.late_frames += 1;
+            self.max_lateness = self.max_lateness.max(lateness);
+        } else {
+            let remaining = self.deadline - now;
+            info!("Sleeping for delay for {} ms", remaining.as_millis());
+            self.clock.sleep(remaining);
+        }
+    }
+
+    /// Log and return how well the animation was played
+    fn report(self, frame_count: usize) -> PlaybackReport {
+        if self.late_frames > 0 || self.skipped_frames > 0 {
+            warn!("Playback fell behind: {} of {} frames were late (up to {} ms), {} skipped",
+                  self.late_frames, frame_count, self.max_lateness.as_millis(), self.skipped_frames);
+        }
+
+        PlaybackReport {
+            late_frames: self.late_frames,
+            skipped_frames: self.skipped_frames,
+            max_lateness: self.max_lateness,
+        }
+    }
+}
+
+/// How long a frame is shown, based on the delay that was encoded with the frame
+fn frame_duration(frame: &Frame) -> Duration {
+    Duration::from_millis((frame.delay as u64) * 10)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::gif::Pixel;
+    use std::fs;
+
+    use tempdir::TempDir;
+
+    use crate::clock::VirtualClock;
+    use crate::gif::fixtures::{blank_animation, RecordingRenderer, write_text_animation};
     use super::*;
 
     #[test]
-    fn test_sleep_frame_delay() {
-        let frame = Frame { pixels: [[Pixel { r: 0, g: 0, b: 0, a: 0 }; 28]; 8], delay: 42 };
-        let start = time::Instant::now();
-        sleep_frame_delay(&frame);
-        let elapsed = start.elapsed();
-        let elapsed_ms = (elapsed.as_secs() * 1_000) + (elapsed.subsec_nanos() / 1_000_000) as u64;
-        assert!(elapsed_ms >= 420 && elapsed_ms <= 430, "Elapsed time: {} ms", elapsed_ms);
+    fn test_play_frames() {
+        let clock = VirtualClock::new();
+        let mut shown: Vec<Duration> = Vec::new();
+
+        //Every frame takes 30 ms to show, which must not add up
+        let report = play_frames(&blank_animation(&[42, 10, 10]).frames, &clock, |_, _| {
+            shown.push(clock.now());
+            clock.advance(Duration::from_millis(30));
+        });
+
+        assert_eq!(shown, vec![Duration::ZERO, Duration::from_millis(420), Duration::from_millis(520)]);
+        assert_eq!(clock.now(), Duration::from_millis(620));
+        assert_eq!(report, PlaybackReport { late_frames: 0, skipped_frames: 0, max_lateness: Duration::ZERO });
+    }
+
+    #[test]
+    fn test_frame_scheduler_late_frames() {
+        let frames = blank_animation(&[10, 10, 10, 10]).frames;
+        let clock = VirtualClock::new();
+        let mut scheduler = FrameScheduler::new(&clock, true);
+
+        //The first frame takes 250 ms to show, so it's late and the next frame is skipped
+        clock.advance(Duration::from_millis(250));
+        scheduler.wait(&frames[0]);
+        assert!(scheduler.should_skip(&frames[1], false));
+        assert!(!scheduler.should_skip(&frames[2], false));
+        scheduler.wait(&frames[2]);
+        assert_eq!(clock.now(), Duration::from_millis(300));
+
+        //The last frame is never skipped
+        clock.advance(Duration::from_millis(500));
+        assert!(!scheduler.should_skip(&frames[3], true));
+
+        let report = scheduler.report(4);
+        assert_eq!(report, PlaybackReport { late_frames: 1, skipped_frames: 1, max_lateness: Duration::from_millis(150) });
+    }
+
+    #[test]
+    fn test_play_rainbow_only_with_overwrite() {
+        let temp_dir = TempDir::new("test_play_rainbow").unwrap();
+        for name in ["free", "forbidden", "fixed"] {
+            write_text_animation(&temp_dir.path().join(format!("{}.txt", name)), &[100]);
+        }
+        fs::write(temp_dir.path().join("forbidden.json"), r#"{"allow_color_overwrite": false}"#).unwrap();
+        fs::write(temp_dir.path().join("fixed.json"), r#"{"color": "FF0080"}"#).unwrap();
+
+        let mut renderer = RecordingRenderer { rainbow: true, ..RecordingRenderer::new() };
+        for name in ["free", "forbidden", "fixed"] {
+            play_animation_from_path(&mut renderer, temp_dir.path().join(format!("{}.txt", name)), None, &Playback::default());
+        }
+        assert_eq!(renderer.played, vec!["rainbow", "play", "colored"]);
+    }
+
+    #[test]
+    fn test_prepared_animation_is_shared() {
+        let temp_dir = TempDir::new("test_prepared_animation").unwrap();
+        let path = temp_dir.path().join("anim.txt");
+        write_text_animation(&path, &[100]);
+        let reverse = Playback { reverse: true, ..Playback::default() };
+
+        let mut renderer = RecordingRenderer::new();
+        play_animation_from_path(&mut renderer, path.clone(), None, &Playback::default());
+        play_animation_from_path(&mut renderer, path.clone(), None, &Playback::default());
+        play_animation_from_path(&mut renderer, path.clone(), None, &reverse);
+
+        //A modified file is read again
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60)).unwrap();
+        play_animation_from_path(&mut renderer, path, None, &reverse);
+
+        assert!(Arc::ptr_eq(&renderer.anims[0], &renderer.anims[1]));
+        assert!(!Arc::ptr_eq(&renderer.anims[1], &renderer.anims[2]));
+        assert!(!Arc::ptr_eq(&renderer.anims[2], &renderer.anims[3]));
+    }
+
+    #[test]
+    fn test_prepared_cache() {
+        let mut cache = PreparedCache::default();
+        let prepared = |path: &str| PreparedAnimation {
+            path: PathBuf::from(path),
+            modified: [None, None],
+            playback: Playback::default(),
+            speed: combined_speed(&Metadata::default(), &Playback::default()),
+            metadata: Metadata::default(),
+            anim: Arc::new(blank_animation(&[])),
+        };
+
+        let paths: Vec<String> = (0..=PREPARED_SIZE).map(|index| format!("{}.gif", index)).collect();
+        for path in &paths {
+            cache.insert(prepared(path));
+        }
+        assert_eq!(cache.entries.len(), PREPARED_SIZE);
+
+        //The least recently used one is dropped, changed files or modifiers aren't used
+        assert!(cache.get(&PathBuf::from(&paths[0]), &[None, None], &Playback::default()).is_none());
+        assert!(cache.get(&PathBuf::from(&paths[1]), &[None, None], &Playback::default()).is_some());
+        assert!(cache.get(&PathBuf::from(&paths[1]), &[Some(SystemTime::now()), None], &Playback::default()).is_none());
+        assert!(cache.get(&PathBuf::from(&paths[1]), &[None, None], &Playback { reverse: true, ..Playback::default() }).is_none());
+        assert_eq!(cache.entries[0].path, PathBuf::from(&paths[1]));
+    }
+
+    #[test]
+    fn test_speed_and_delay_policy() {
+        let temp_dir = TempDir::new("test_speed_and_delay_policy").unwrap();
+        for name in ["clamped", "fast"] {
+            write_text_animation(&temp_dir.path().join(format!("{}.txt", name)), &[100]);
+        }
+        write_text_animation(&temp_dir.path().join("tiny.txt"), &[0]);
+        fs::write(temp_dir.path().join("clamped.json"), r#"{"min_frame_delay": 50}"#).unwrap();
+        fs::write(temp_dir.path().join("fast.json"), r#"{"speed": 20.0, "min_frame_delay": 0}"#).unwrap();
+
+        let mut renderer = RecordingRenderer::new();
+        let speed = |speed| Playback { speed: Some(speed), ..Playback::default() };
+        play_animation_from_path(&mut renderer, temp_dir.path().join("clamped.txt"), None, &speed(4.0));
+        play_animation_from_path(&mut renderer, temp_dir.path().join("fast.txt"), None, &speed(5.0));
+        play_animation_from_path(&mut renderer, temp_dir.path().join("tiny.txt"), None, &speed(4.0));
+
+        //25 ms are raised to the minimum delay, a speed of 100 is clamped to 10
+        assert_eq!(renderer.anims[0].frames[0].delay, 5);
+        assert_eq!(renderer.anims[1].frames[0].delay, 1);
+
+        //The 100 ms browsers use for a delay of 0 are sped up like any other delay
+        assert_eq!(renderer.anims[2].frames[0].delay, 3);
+    }
+
+    #[test]
+    fn test_apply_delay_policy() {
+        let mut anim = blank_animation(&[0, 1, 2, 10]);
+        apply_delay_policy(&mut anim, DelayPolicy::Keep, 50);
+        assert_eq!(anim.frames.iter().map(|f| f.delay).collect::<Vec<u16>>(), vec![0, 1, 2, 10]);
+
+        apply_delay_policy(&mut anim, DelayPolicy::Clamp, 15);
+        assert_eq!(anim.frames.iter().map(|f| f.delay).collect::<Vec<u16>>(), vec![2, 2, 2, 10]);
+
+        let mut anim = blank_animation(&[0, 1, 2, 10]);
+        apply_delay_policy(&mut anim, DelayPolicy::Browser, 50);
+        assert_eq!(anim.frames.iter().map(|f| f.delay).collect::<Vec<u16>>(), vec![10, 10, 2, 10]);
+    }
+
+    #[test]
+    fn test_apply_speed() {
+        let mut anim = blank_animation(&[10, 5]);
+
+        apply_speed(&mut anim, 2.0);
+        assert_eq!(anim.frames[0].delay, 5);
+        assert_eq!(anim.frames[1].delay, 3);
+
+        apply_speed(&mut anim, 0.0);
+        assert_eq!(anim.frames[0].delay, 5);
+
+        apply_speed(&mut anim, 0.5);
+        assert_eq!(anim.frames[0].delay, 10);
     }
 }
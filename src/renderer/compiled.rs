@@ -0,0 +1,232 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use rs_ws281x::RawColor;
+
+use crate::color::{Color, get_gamma_correction};
+use crate::color_mapping::ColorMapper;
+use crate::gif::{Animation, Frame, Pixel};
+use crate::renderer::tasbot_eyes::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+/// How many compiled animations are kept, so base and blinks aren't compiled again in every cycle
+pub const CACHE_SIZE: usize = 8;
+
+/// The gamma corrected values of all channel values, so each is calculated only once instead of once per pixel
+pub struct GammaTable {
+    /// The gamma value, if gamma correction is used
+    gamma: Option<f32>,
+
+    /// The corrected value of every channel value
+    values: [u8; 256],
+}
+
+impl GammaTable {
+    /// Calculate the table for a gamma value
+    ///
+    /// # Input
+    /// `gamma`: The gamma value, if gamma correction is to use. Without, every value stays as it is
+    pub fn new(gamma: Option<f32>) -> Self {
+        let mut values: [u8; 256] = [0; 256];
+        for (value, corrected) in values.iter_mut().enumerate() {
+            *corrected = gamma.map_or(value as u8, |g| get_gamma_correction(value as u8, g));
+        }
+
+        GammaTable { gamma, values }
+    }
+
+    /// Correct the color channels of an LED
+    fn correct(&self, raw: RawColor) -> RawColor {
+        [self.values[raw[0] as usize], self.values[raw[1] as usize], self.values[raw[2] as usize], 0]
+    }
+}
+
+/// Convert a frame into the LED buffer of a display
+///
+/// # Input
+/// * `frame`: The `Frame` that is to convert
+/// * `colorize`: Gets the color of a pixel by its column
+/// * `gamma`: The `GammaTable` the colors are corrected with
+/// * `positions`: The translation of every pixel to the index of its LED, `None` if it has no LED
+/// * `leds`: The LED buffer, that is written
+pub fn compile_frame(
+    frame: &Frame,
+    colorize: &dyn Fn(usize, &Pixel) -> Color,
+    gamma: &GammaTable,
+    positions: &[[Option<usize>; SCREEN_WIDTH]; SCREEN_HEIGHT],
+    leds: &mut [RawColor],
+) {
+    for (y, row) in positions.iter().enumerate() {
+        for (x, index) in row.iter().enumerate() {
+            let Some(index) = index else { continue };
+            leds[*index] = gamma.correct(colorize(x, &frame.pixels[y][x]).to_raw());
+        }
+    }
+}
+
+/// An animation, that is already converted to the LED buffers of a display. Showing a frame is only a
+/// copy of its buffer then
+pub struct CompiledAnimation {
+    /// The animation this was compiled from
+    source: Arc<Animation>,
+
+    /// The color overwrite this was compiled with
+    color: Option<Color>,
+
+    /// How the pixels were mapped onto the color overwrite
+    mapper: ColorMapper,
+
+    /// The gamma value this was compiled with, if gamma correction is used
+    gamma: Option<f32>,
+
+    /// One LED buffer per frame of the animation
+    pub frames: Vec<Vec<RawColor>>,
+}
+
+impl CompiledAnimation {
+    /// Compile an animation into LED buffers
+    ///
+    /// # Input
+    /// * `anim`: The `Animation` that is to compile
+    /// * `color`: An optional color to overwrite the color of the animation
+    /// * `mapper`: How the pixels are mapped onto the overwrite color
+    /// * `gamma`: The `GammaTable` the colors are corrected with
+    /// * `positions`: The translation of every pixel to the index of its LED, `None` if it has no LED
+    /// * `led_count`: The count of LEDs of the display
+    ///
+    /// # Output
+    /// The `CompiledAnimation` with one buffer of `led_count` LEDs per frame
+    pub fn compile(
+        anim: &Arc<Animation>,
+        color: Option<&Color>,
+        mapper: &ColorMapper,
+        gamma: &GammaTable,
+        positions: &[[Option<usize>; SCREEN_WIDTH]; SCREEN_HEIGHT],
+        led_count: usize,
+    ) -> Self {
+        let frames = anim.frames.iter().map(|frame| {
+            let colorize = |_: usize, pixel: &Pixel| match color {
+                None => Color { r: pixel.r, g: pixel.g, b: pixel.b },
+                Some(color) => mapper.colorize(frame, pixel, color),
+            };
+
+            let mut leds: Vec<RawColor> = vec![[0, 0, 0, 0]; led_count];
+            compile_frame(frame, &colorize, gamma, positions, &mut leds);
+            leds
+        }).collect();
+
+        CompiledAnimation { source: anim.clone(), color: color.copied(), mapper: mapper.clone(), gamma: gamma.gamma, frames }
+    }
+
+    /// Check if this was compiled from exactly this animation and with the same settings. The animations
+    /// are shared per file, see `play_animation_from_path()`
+    pub fn is_compiled_from(&self, anim: &Arc<Animation>, color: Option<&Color>, mapper: &ColorMapper, gamma: Option<f32>) -> bool {
+        Arc::ptr_eq(&self.source, anim) && self.color.as_ref() == color && &self.mapper == mapper && self.gamma == gamma
+    }
+}
+
+/// The last compiled animations, the most recently used first
+pub struct CompiledCache {
+    entries: VecDeque<Arc<CompiledAnimation>>,
+
+    /// The `GammaTable` of the last used gamma value
+    gamma: GammaTable,
+}
+
+impl Default for CompiledCache {
+    fn default() -> Self {
+        CompiledCache { entries: VecDeque::new(), gamma: GammaTable::new(None) }
+    }
+}
+
+impl CompiledCache {
+    /// Get the `GammaTable` for a gamma value. It's only calculated again, if the gamma value changed
+    pub fn gamma_table(&mut self, gamma: Option<f32>) -> &GammaTable {
+        if self.gamma.gamma != gamma {
+            self.gamma = GammaTable::new(gamma);
+        }
+
+        &self.gamma
+    }
+
+    /// Get a compiled animation from the cache or compile it, if it isn't cached yet. The arguments are the
+    /// ones of `CompiledAnimation::compile()`
+    ///
+    /// # Output
+    /// The `CompiledAnimation`
+    pub fn get(
+        &mut self,
+        anim: &Arc<Animation>,
+        color: Option<&Color>,
+        mapper: &ColorMapper,
+        gamma: Option<f32>,
+        positions: &[[Option<usize>; SCREEN_WIDTH]; SCREEN_HEIGHT],
+        led_count: usize,
+    ) -> Arc<CompiledAnimation> {
+        let compiled = match self.entries.iter().position(|c| c.is_compiled_from(anim, color, mapper, gamma)) {
+            Some(index) => self.entries.remove(index).unwrap(),
+            None => Arc::new(CompiledAnimation::compile(anim, color, mapper, self.gamma_table(gamma), positions, led_count)),
+        };
+
+        self.entries.push_front(compiled.clone());
+        self.entries.truncate(CACHE_SIZE);
+        compiled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color_mapping::ColorMapping;
+    use crate::gif::Frame;
+    use crate::gif::fixtures::{animation, blank_animation};
+    use crate::renderer::tasbot_eyes::{NUM_PIXELS, PIXEL_POSITIONS};
+
+    use super::*;
+
+    #[test]
+    fn test_compile() {
+        let anim = Arc::new(animation(vec![Frame::dotted(&[(2, 0, Color { r: 10, g: 20, b: 30 })], 5)]));
+        let mapper = ColorMapper::new(ColorMapping::Flat, Vec::new());
+
+        let compiled = CompiledAnimation::compile(&anim, None, &mapper, &GammaTable::new(None), &PIXEL_POSITIONS, NUM_PIXELS as usize);
+        assert_eq!(compiled.frames.len(), 1);
+        assert_eq!(compiled.frames[0].len(), NUM_PIXELS as usize);
+        assert_eq!(compiled.frames[0][0], [30, 20, 10, 0]);
+        assert_eq!(compiled.frames[0][1], [0, 0, 0, 0]);
+
+        let red = Color { r: 255, g: 0, b: 0 };
+        let colored = CompiledAnimation::compile(&anim, Some(&red), &mapper, &GammaTable::new(None), &PIXEL_POSITIONS, NUM_PIXELS as usize);
+        assert_eq!(colored.frames[0][0], [0, 0, 255, 0]);
+
+        let corrected = CompiledAnimation::compile(&anim, None, &mapper, &GammaTable::new(Some(2.0)), &PIXEL_POSITIONS, NUM_PIXELS as usize);
+        assert_eq!(corrected.frames[0][0], [get_gamma_correction(30, 2.0), get_gamma_correction(20, 2.0), get_gamma_correction(10, 2.0), 0]);
+
+        assert!(compiled.is_compiled_from(&anim, None, &mapper, None));
+        assert!(!compiled.is_compiled_from(&anim, Some(&red), &mapper, None));
+        assert!(!compiled.is_compiled_from(&anim, None, &ColorMapper::new(ColorMapping::Luminance, Vec::new()), None));
+        assert!(!compiled.is_compiled_from(&Arc::new((*anim).clone()), None, &mapper, None));
+    }
+
+    #[test]
+    fn test_compiled_cache() {
+        let anims: Vec<Arc<Animation>> = (0..=CACHE_SIZE).map(|_| Arc::new(blank_animation(&[]))).collect();
+        let mapper = ColorMapper::new(ColorMapping::Flat, Vec::new());
+        let mut cache = CompiledCache::default();
+
+        let first = cache.get(&anims[0], None, &mapper, None, &PIXEL_POSITIONS, NUM_PIXELS as usize);
+        assert!(Arc::ptr_eq(&first, &cache.get(&anims[0], None, &mapper, None, &PIXEL_POSITIONS, NUM_PIXELS as usize)));
+
+        //The least recently used one is dropped
+        for anim in &anims[1..] {
+            cache.get(anim, None, &mapper, None, &PIXEL_POSITIONS, NUM_PIXELS as usize);
+        }
+        assert!(!Arc::ptr_eq(&first, &cache.get(&anims[0], None, &mapper, None, &PIXEL_POSITIONS, NUM_PIXELS as usize)));
+        assert_eq!(cache.entries.len(), CACHE_SIZE);
+
+        //Frames colored while showing them use the same gamma table
+        let table = cache.gamma_table(Some(2.0));
+        assert_eq!(table.correct([128, 255, 255, 0]), [get_gamma_correction(128, 2.0), 255, 255, 0]);
+        let mut leds: Vec<RawColor> = vec![[0, 0, 0, 0]; NUM_PIXELS as usize];
+        compile_frame(&Frame::blank(5), &|x, _| Color { r: x as u8, g: 0, b: 0 }, cache.gamma_table(None), &PIXEL_POSITIONS, &mut leds);
+        assert_eq!(leds[0], [0, 0, 2, 0]);
+    }
+}
@@ -1,12 +1,17 @@
 use std::io;
 use std::fmt::{Display, Formatter};
 use std::io::Write;
+use std::sync::Arc;
+use std::time::Duration;
 
 use colored::Colorize;
 use log::info;
 
-use crate::gif::{Animation, Frame, pixel_is_black};
-use crate::renderer::{Color, Renderer, sleep_frame_delay};
+use crate::clock::Clock;
+use crate::color_mapping::ColorMapper;
+use crate::gif::{Animation, Frame, Pixel, pixel_is_black};
+use crate::rainbow::{RainbowPlayer, REFRESH_INTERVAL};
+use crate::renderer::{Color, play_frames, Renderer};
 
 ///The character, which is printed for pixel that arent black
 const FILLED_CHARACTERS: &str = "██";
@@ -17,6 +22,13 @@ const EMPTY_CHARACTERS: &str = "  ";
 /// Configuration for the console renderer
 pub struct ConsoleRendererSettings {
     pub clear_console: bool,
+    pub clock: Arc<dyn Clock>,
+
+    /// How the pixels are mapped onto overwrite colors
+    pub mapper: ColorMapper,
+
+    /// Colors grayscale animations by a moving hue, if set
+    pub rainbow: Option<RainbowPlayer>,
 }
 
 impl Display for ConsoleRendererSettings {
@@ -30,27 +42,64 @@ impl Display for ConsoleRendererSettings {
 }
 
 impl Renderer for ConsoleRendererSettings {
-    fn play(&mut self, anim: Animation) {
-        for frame in &anim.frames {
-            show_frame(self, frame, None);
-        }
+    fn play(&mut self, anim: Arc<Animation>) {
+        forget_rainbow(self);
+        let clock = self.clock.clone();
+        play_frames(&anim.frames, clock.as_ref(), |_, frame| show_frame(self, frame, &own_color));
     }
 
-    fn play_colored(&mut self, anim: Animation, color: &Color) {
-        let color = if anim.grayscale { Some(color) } else { None };
+    fn play_colored(&mut self, anim: Arc<Animation>, color: &Color) {
+        let use_color = self.mapper.colorizes(&anim) || self.mapper.blends();
 
-        for frame in &anim.frames {
-            show_frame(self, frame, color);
-        }
+        forget_rainbow(self);
+        let clock = self.clock.clone();
+        play_frames(&anim.frames, clock.as_ref(), |_, frame| {
+            let colorize = |_: usize, pixel: &Pixel| if use_color { self.mapper.colorize(frame, pixel, color) } else { own_color(0, pixel) };
+            show_frame(self, frame, &colorize);
+        });
     }
 
     fn clear(&mut self) {
+        forget_rainbow(self);
         clear_console();
     }
 
     fn print_config(&self) {
         info!("Start console renderer using those arguments:\n{}", self);
     }
+
+    fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    fn refresh_interval(&self) -> Option<Duration> {
+        //Without clearing the console, every refresh would print the frame below the last one
+        self.rainbow.as_ref().filter(|_| self.clear_console).map(|_| REFRESH_INTERVAL)
+    }
+
+    fn refresh(&mut self) {
+        if let Some(rainbow) = self.rainbow.take() {
+            let clock = self.clock.clone();
+            rainbow.refresh(clock.as_ref(), &mut |frame, colorize| show_frame(self, frame, colorize));
+            self.rainbow = Some(rainbow);
+        }
+    }
+
+    fn play_rainbow(&mut self, anim: &Arc<Animation>) -> bool {
+        let Some(mut rainbow) = self.rainbow.take() else { return false };
+
+        let clock = self.clock.clone();
+        let played = rainbow.play(anim, clock.as_ref(), &mut |frame, colorize| show_frame(self, frame, colorize));
+        self.rainbow = Some(rainbow);
+        played
+    }
+}
+
+/// Forget the last frame shown with the rainbow, as something else is shown now
+fn forget_rainbow(settings: &mut ConsoleRendererSettings) {
+    if let Some(rainbow) = &mut settings.rainbow {
+        rainbow.forget();
+    }
 }
 
 /// Handle a frame that's to be rendered in the console
@@ -58,37 +107,28 @@ impl Renderer for ConsoleRendererSettings {
 /// # Input
 /// `settings`: The configuration that should be used for rendering
 /// `frame`: The `Frame` that should be rendered
-/// `color`: An optional color to overwrite the frames own color
-fn show_frame(settings: &ConsoleRendererSettings, frame: &Frame, color: Option<&Color>) {
+/// `colorize`: Gets the color of a pixel by its column
+fn show_frame(settings: &ConsoleRendererSettings, frame: &Frame, colorize: &dyn Fn(usize, &Pixel) -> Color) {
     //clear console
     if settings.clear_console {
         clear_console();
     }
 
     //render frame
-    render_frame(frame, color);
-
-    //sleep base on delay from gif
-    sleep_frame_delay(frame);
+    render_frame(frame, colorize);
 }
 
 /// Render the frame in the console
 ///
 /// # Input
 /// `frame`: The `Frame` that's gonna be rendered
-/// `color`: An optional color to overwrite the frames own color
-fn render_frame(frame: &Frame, color: Option<&Color>) {
+/// `colorize`: Gets the color of a pixel by its column
+fn render_frame(frame: &Frame, colorize: &dyn Fn(usize, &Pixel) -> Color) {
     for row in frame.pixels {
-        for pixel in row {
-            if !pixel_is_black(&pixel) {
-                match color {
-                    None => {
-                        print!("{}", FILLED_CHARACTERS.truecolor(pixel.r, pixel.g, pixel.b));
-                    }
-                    Some(col) => {
-                        print!("{}", FILLED_CHARACTERS.truecolor(col.r, col.g, col.b));
-                    }
-                }
+        for (x, pixel) in row.iter().enumerate() {
+            if !pixel_is_black(pixel) {
+                let col = colorize(x, pixel);
+                print!("{}", FILLED_CHARACTERS.truecolor(col.r, col.g, col.b));
             } else {
                 print!("{EMPTY_CHARACTERS}");
             }
@@ -99,6 +139,11 @@ fn render_frame(frame: &Frame, color: Option<&Color>) {
     info!("Rendering okay")
 }
 
+/// The own color of a pixel, for animations that aren't colored
+fn own_color(_x: usize, pixel: &Pixel) -> Color {
+    Color { r: pixel.r, g: pixel.g, b: pixel.b }
+}
+
 ///Clear the console by printing several empty lines.
 fn clear_console() {
     print!("{}[2J", 27 as char);
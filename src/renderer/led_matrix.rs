@@ -5,16 +5,18 @@
  */
 
 use std::fmt::{Display, Formatter};
+use std::sync::Arc;
 use std::ops::Add;
 
 use log::{info, warn};
 use rs_ws281x::{Controller, RawColor, StripType};
 use thiserror::Error;
 
+use crate::clock::Clock;
 use crate::color::{BLACK, Color};
 use crate::gif::{Animation, Frame, pixel_is_black};
 use crate::led::LEDHardwareConfig;
-use crate::renderer::{Renderer, sleep_frame_delay};
+use crate::renderer::{play_frames, Renderer};
 use crate::renderer::tasbot_eyes::{SCREEN_HEIGHT, SCREEN_WIDTH};
 
 //default values but not fixed
@@ -33,6 +35,7 @@ pub struct LEDMatrixRenderer {
     pub controller: Controller,
     pub gamma_correction: bool,
     pub gamma: f32,
+    pub clock: Arc<dyn Clock>,
 }
 
 impl Display for LEDMatrixRenderer {
@@ -47,14 +50,13 @@ impl Display for LEDMatrixRenderer {
 }
 
 impl Renderer for LEDMatrixRenderer {
-    fn play(&mut self, anim: Animation) {
+    fn play(&mut self, anim: Arc<Animation>) {
         todo!();
-        for frame in &anim.frames {
-            show_frame(self, frame, None);
-        }
+        let clock = self.clock.clone();
+        play_frames(&anim.frames, clock.as_ref(), |_, frame| show_frame(self, frame, None));
     }
 
-    fn play_colored(&mut self, anim: Animation, color: &Color) {
+    fn play_colored(&mut self, anim: Arc<Animation>, color: &Color) {
         todo!();
         let color = if anim.grayscale { Some(color) } else { None };
 
@@ -72,6 +74,10 @@ impl Renderer for LEDMatrixRenderer {
         todo!();
         info!("Start matrix renderer using those arguments:\n{}", self);
     }
+
+    fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
 }
 
 fn show_frame(settings: &mut LEDMatrixRenderer, frame: &Frame, color: Option<&Color>) {
@@ -109,9 +115,6 @@ fn show_frame(settings: &mut LEDMatrixRenderer, frame: &Frame, color: Option<&Co
 
     //Render
     render(settings);
-
-    //Sleep
-    sleep_frame_delay(frame);
 }
 
 fn clear(settings: &mut LEDMatrixRenderer) {
@@ -1,19 +1,26 @@
+use std::sync::Arc;
+
 use log::debug;
 
+use crate::clock::Clock;
 use crate::gif::Animation;
 use crate::renderer::{Color, Renderer};
 
 /// Dummy settings
-pub struct SilentRendererSettings;
+pub struct SilentRendererSettings {
+    pub clock: Arc<dyn Clock>,
+}
 
 impl Renderer for SilentRendererSettings {
-    fn play(&mut self, _anim: Animation) {
+    fn play(&mut self, _anim: Arc<Animation>) {
         debug!("Silent renderer")
     }
 
-    fn play_colored(&mut self, _anim: Animation, _color: &Color) { debug!("Silent renderer with color overwrite") }
+    fn play_colored(&mut self, _anim: Arc<Animation>, _color: &Color) { debug!("Silent renderer with color overwrite") }
 
     fn clear(&mut self) { debug!("Clear console") }
 
     fn print_config(&self) { debug!("No config for silent renderer needed") }
+
+    fn clock(&self) -> &dyn Clock { self.clock.as_ref() }
 }
\ No newline at end of file
@@ -1,12 +1,18 @@
 use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use std::time::Duration;
 
 use log::{info, warn};
 use rs_ws281x::{Controller, RawColor, StripType};
 
-use crate::color::{BLACK, Color, get_gamma_correction};
-use crate::gif::{Animation, Frame, pixel_is_black};
+use crate::clock::Clock;
+use crate::color::Color;
+use crate::color_mapping::ColorMapper;
+use crate::gif::{Animation, Frame, Pixel};
 use crate::led::LEDHardwareConfig;
-use crate::renderer::{Renderer, sleep_frame_delay};
+use crate::rainbow::{RainbowPlayer, REFRESH_INTERVAL};
+use crate::renderer::{play_frames, Renderer};
+use crate::renderer::compiled::{compile_frame, CompiledCache};
 
 //fixed values
 /// The frequency the for the pulse (i.e., rectangular) wave signal for TASBot
@@ -60,6 +66,16 @@ pub struct TASBotRendererSettings {
     pub controller: Controller,
     pub gamma_correction: bool,
     pub gamma: f32,
+    pub clock: Arc<dyn Clock>,
+
+    /// The last compiled animations, so animations that are played again aren't compiled again
+    pub compiled: CompiledCache,
+
+    /// How the pixels are mapped onto overwrite colors
+    pub mapper: ColorMapper,
+
+    /// Colors grayscale animations by a moving hue, if set
+    pub rainbow: Option<RainbowPlayer>,
 }
 
 impl Display for TASBotRendererSettings {
@@ -76,18 +92,13 @@ impl Display for TASBotRendererSettings {
 }
 
 impl Renderer for TASBotRendererSettings {
-    fn play(&mut self, anim: Animation) {
-        for frame in &anim.frames {
-            show_frame(self, frame, None);
-        }
+    fn play(&mut self, anim: Arc<Animation>) {
+        play_compiled(self, &anim, None);
     }
 
-    fn play_colored(&mut self, anim: Animation, color: &Color) {
-        let color = if anim.grayscale { Some(color) } else { None };
-
-        for frame in &anim.frames {
-            show_frame(self, frame, color);
-        }
+    fn play_colored(&mut self, anim: Arc<Animation>, color: &Color) {
+        let color = if self.mapper.colorizes(&anim) || self.mapper.blends() { Some(color) } else { None };
+        play_compiled(self, &anim, color);
     }
 
     fn clear(&mut self) {
@@ -95,72 +106,79 @@ impl Renderer for TASBotRendererSettings {
     }
 
     fn print_config(&self) { info!("Start TASBot renderer using those arguments:\n{}", self); }
+
+    fn clock(&self) -> &dyn Clock { self.clock.as_ref() }
+
+    fn refresh_interval(&self) -> Option<Duration> { self.rainbow.as_ref().map(|_| REFRESH_INTERVAL) }
+
+    fn refresh(&mut self) {
+        if let Some(rainbow) = self.rainbow.take() {
+            let clock = self.clock.clone();
+            rainbow.refresh(clock.as_ref(), &mut |frame, colorize| show_colorized(self, frame, colorize));
+            self.rainbow = Some(rainbow);
+        }
+    }
+
+    fn play_rainbow(&mut self, anim: &Arc<Animation>) -> bool {
+        let Some(mut rainbow) = self.rainbow.take() else { return false };
+
+        //The colors change over time, so the frames can't be compiled
+        let clock = self.clock.clone();
+        let played = rainbow.play(anim, clock.as_ref(), &mut |frame, colorize| show_colorized(self, frame, colorize));
+        self.rainbow = Some(rainbow);
+        played
+    }
 }
 
-/// Handle a frame that's to be rendered in the console
+/// Play an animation by its LED buffers. The animation is only compiled, if it isn't cached
 ///
 /// # Input
 /// `settings`: The configuration that should be used for rendering
-/// `frame`: The `Frame` that should be rendered
+/// `anim`: The `Animation` that should be played
 /// `color`: An optional color to overwrite the frames own color
-fn show_frame(settings: &mut TASBotRendererSettings, frame: &Frame, color: Option<&Color>) {
-    let leds = settings.controller.leds_mut(0);
+fn play_compiled(settings: &mut TASBotRendererSettings, anim: &Arc<Animation>, color: Option<&Color>) {
+    let gamma = if settings.gamma_correction { Some(settings.gamma) } else { None };
 
-    //Index based for loops, as we need the index for the translation
-    for x in 0..SCREEN_WIDTH {
-        for y in 0..SCREEN_HEIGHT {
-            let index = PIXEL_POSITIONS[y][x];
-            match index {
-                None => {}
-                Some(index) => {
-                    let mut rend_color: RawColor;
-                    match color {
-
-                        //Use color of frame
-                        None => {
-                            rend_color = [
-                                frame.pixels[y][x].b,
-                                frame.pixels[y][x].g,
-                                frame.pixels[y][x].r,
-                                0,
-                            ];
-                        }
-
-                        //Use color given
-                        Some(color) => {
-                            if pixel_is_black(&frame.pixels[y][x]) {
-                                rend_color = BLACK.to_raw();
-                            } else {
-                                rend_color = color.to_raw();
-                            }
-                        }
-                    }
-
-                    if settings.gamma_correction {
-                        let corrected_color: Vec<u8> = rend_color.iter().map(|v| get_gamma_correction(*v, settings.gamma)).collect();
-
-                        rend_color = [
-                            corrected_color[0],
-                            corrected_color[1],
-                            corrected_color[2],
-                            0,
-                        ];
-                    }
-
-                    leds[index] = rend_color;
-                }
-            }
-        }
+    let compiled = settings.compiled.get(anim, color, &settings.mapper, gamma, &PIXEL_POSITIONS, NUM_PIXELS as usize);
+
+    if let Some(rainbow) = &mut settings.rainbow {
+        rainbow.forget();
     }
+    let clock = settings.clock.clone();
+    play_frames(&anim.frames, clock.as_ref(), |index, _| show_frame(settings, &compiled.frames[index]));
+}
+
+/// Show a frame, whose colors are determined while showing it, like the ones of the rainbow. It's compiled
+/// into the LED buffer of the controller like the frames of `play_compiled()`
+///
+/// # Input
+/// `settings`: The configuration that should be used for rendering
+/// `frame`: The `Frame` that should be rendered
+/// `colorize`: Gets the color of a pixel by its column
+fn show_colorized(settings: &mut TASBotRendererSettings, frame: &Frame, colorize: &dyn Fn(usize, &Pixel) -> Color) {
+    let gamma = if settings.gamma_correction { Some(settings.gamma) } else { None };
+    let gamma_table = settings.compiled.gamma_table(gamma);
+    compile_frame(frame, colorize, gamma_table, &PIXEL_POSITIONS, settings.controller.leds_mut(0));
 
-    //Render
     render(settings);
+}
+
+/// Show the LED buffer of a frame
+///
+/// # Input
+/// `settings`: The configuration that should be used for rendering
+/// `leds`: The compiled LED buffer of the frame
+fn show_frame(settings: &mut TASBotRendererSettings, leds: &[RawColor]) {
+    settings.controller.leds_mut(0).copy_from_slice(leds);
 
-    //Sleep
-    sleep_frame_delay(frame);
+    //Render
+    render(settings);
 }
 
 fn clear(settings: &mut TASBotRendererSettings) {
+    if let Some(rainbow) = &mut settings.rainbow {
+        rainbow.forget();
+    }
     let leds = settings.controller.leds_mut(0);
     for led in leds {
         *led = [0, 0, 0, 0];
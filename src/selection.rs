@@ -0,0 +1,269 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use crate::library::LibraryEntry;
+
+/// How often an animation is put into the bag of `SelectionStrategy::ShuffleBag` at most, so a tiny weight
+/// of another animation can't make the bag huge
+pub const MAX_COPIES: usize = 100;
+
+#[derive(clap::ValueEnum, Copy, Clone, PartialEq, Debug)]
+/// How the next animation is chosen from the library
+pub enum SelectionStrategy {
+    /// Every animation is equally likely, the weight of the metadata only excludes animations with a weight of 0
+    Uniform,
+
+    /// Animations are chosen by the weight of their metadata
+    Weighted,
+
+    /// Like `Weighted`, but none of the last N played animations is chosen again
+    NoRepeat,
+
+    /// Like `Weighted`, but the longer an animation wasn't played, the more likely it gets
+    LeastRecent,
+
+    /// Every animation is played once (or as often as its weight compared to the others says) in a shuffled order,
+    /// before any repeats
+    ShuffleBag,
+}
+
+impl Display for SelectionStrategy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            SelectionStrategy::Uniform => "uniform",
+            SelectionStrategy::Weighted => "weighted",
+            SelectionStrategy::NoRepeat => "no-repeat",
+            SelectionStrategy::LeastRecent => "least-recent",
+            SelectionStrategy::ShuffleBag => "shuffle-bag",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Chooses animations by a `SelectionStrategy` and remembers what was played before
+pub struct Selector {
+    /// The strategy that is used
+    strategy: SelectionStrategy,
+
+    /// How many of the last played animations are excluded by `NoRepeat`
+    window: usize,
+
+    /// The last played animations, the newest one is at the back
+    history: VecDeque<PathBuf>,
+
+    /// When each animation was played the last time, counted in selections
+    last_played: HashMap<PathBuf, u64>,
+
+    /// Count of all selections so far
+    selections: u64,
+
+    /// The remaining animations of the current bag for `ShuffleBag`
+    bag: Vec<PathBuf>,
+}
+
+impl Selector {
+    /// Create a new selector without any history
+    ///
+    /// # Input
+    /// * `strategy`: The `SelectionStrategy` to use
+    /// * `window`: How many of the last played animations are excluded by `NoRepeat`
+    pub fn new(strategy: SelectionStrategy, window: usize) -> Self {
+        Selector {
+            strategy,
+            window,
+            history: VecDeque::new(),
+            last_played: HashMap::new(),
+            selections: 0,
+            bag: Vec::new(),
+        }
+    }
+
+    /// Choose the next animation and remember it as played
+    ///
+    /// # Input
+    /// * `entries`: All animations, that can be chosen
+    /// * `rng`: The random number generator to use
+    ///
+    /// # Output
+    /// The path to the chosen animation or `None`, if there's nothing to choose from
+    pub fn select<R: Rng>(&mut self, entries: &[&LibraryEntry], rng: &mut R) -> Option<PathBuf> {
+        let chosen = match self.strategy {
+            SelectionStrategy::Uniform => choose_weighted(entries, rng, |entry| if entry.weight > 0.0 { 1.0 } else { 0.0 }),
+            SelectionStrategy::Weighted => choose_weighted(entries, rng, |entry| entry.weight),
+            SelectionStrategy::NoRepeat => self.select_no_repeat(entries, rng),
+            SelectionStrategy::LeastRecent => {
+                let now = self.selections;
+                choose_weighted(entries, rng, |entry| {
+                    let age = self.last_played.get(&entry.path).map_or(now + 1, |played| now - played);
+                    entry.weight * age as f32
+                })
+            }
+            SelectionStrategy::ShuffleBag => self.select_from_bag(entries, rng),
+        }?;
+
+        self.remember(chosen.clone());
+        Some(chosen)
+    }
+
+    /// Choose a weighted animation, that is not within the history window. If all animations are within
+    /// it, the one played the longest ago is chosen
+    fn select_no_repeat<R: Rng>(&self, entries: &[&LibraryEntry], rng: &mut R) -> Option<PathBuf> {
+        let recent: Vec<&PathBuf> = self.history.iter().rev().take(self.window).collect();
+        let candidates: Vec<&LibraryEntry> = entries.iter()
+            .filter(|entry| !recent.contains(&&entry.path))
+            .copied()
+            .collect();
+
+        choose_weighted(&candidates, rng, |entry| entry.weight).or_else(|| {
+            entries.iter()
+                .filter(|entry| entry.weight > 0.0)
+                .min_by_key(|entry| self.last_played.get(&entry.path).copied().unwrap_or(0))
+                .map(|entry| entry.path.clone())
+        })
+    }
+
+    /// Take the next animation out of the bag and refill it, when it's empty
+    fn select_from_bag<R: Rng>(&mut self, entries: &[&LibraryEntry], rng: &mut R) -> Option<PathBuf> {
+        //Animations might have been removed since the bag was filled
+        self.bag.retain(|path| entries.iter().any(|entry| entry.path == *path));
+
+        if self.bag.is_empty() {
+            self.bag = fill_bag(entries);
+            self.bag.shuffle(rng);
+
+            //Don't play the last animation of the previous bag twice in a row. The bag is taken from the back
+            //and can contain the same path several times, so it's swapped with any other path
+            if self.bag.last().is_some_and(|last| Some(last) == self.history.back()) {
+                if let Some(other) = self.bag.iter().position(|path| Some(path) != self.history.back()) {
+                    let last = self.bag.len() - 1;
+                    self.bag.swap(other, last);
+                }
+            }
+        }
+
+        self.bag.pop()
+    }
+
+    /// Remember an animation as played
+    fn remember(&mut self, path: PathBuf) {
+        self.last_played.insert(path.clone(), self.selections);
+        self.selections += 1;
+
+        self.history.push_back(path);
+        while self.history.len() > self.window.max(1) {
+            self.history.pop_front();
+        }
+    }
+}
+
+/// Choose a random animation by a weight function. Animations with a weight of 0 (or less) are never chosen
+fn choose_weighted<R: Rng, F: Fn(&LibraryEntry) -> f32>(entries: &[&LibraryEntry], rng: &mut R, weight: F) -> Option<PathBuf> {
+    entries.choose_weighted(rng, |entry| weight(entry).max(0.0))
+        .ok()
+        .map(|entry| entry.path.clone())
+}
+
+/// Fill a bag with all animations, that have a weight. The smallest weight is put in once and every other
+/// animation as often as its weight compared to the smallest one says, but at most `MAX_COPIES` times
+fn fill_bag(entries: &[&LibraryEntry]) -> Vec<PathBuf> {
+    let smallest = entries.iter()
+        .map(|entry| entry.weight)
+        .filter(|weight| *weight > 0.0)
+        .min_by(|a, b| a.total_cmp(b));
+    let Some(smallest) = smallest else { return Vec::new() };
+
+    let mut bag: Vec<PathBuf> = Vec::new();
+    for entry in entries.iter().filter(|entry| entry.weight > 0.0) {
+        let copies = (entry.weight / smallest).round().clamp(1.0, MAX_COPIES as f32) as usize;
+        bag.extend(vec![entry.path.clone(); copies]);
+    }
+
+    bag
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn entries(weights: &[f32]) -> Vec<LibraryEntry> {
+        weights.iter()
+            .enumerate()
+            .map(|(i, weight)| LibraryEntry { path: PathBuf::from(format!("{}.gif", i)), tags: Vec::new(), weight: *weight })
+            .collect()
+    }
+
+    #[test]
+    fn test_select_no_repeat() {
+        let entries = entries(&[1.0, 1.0, 1.0]);
+        let entries: Vec<&LibraryEntry> = entries.iter().collect();
+        let mut selector = Selector::new(SelectionStrategy::NoRepeat, 2);
+        let mut rng = StdRng::seed_from_u64(42);
+
+        //With a window of 2 out of 3 animations, every three selections are all different
+        let chosen: Vec<PathBuf> = (0..30).map(|_| selector.select(&entries, &mut rng).unwrap()).collect();
+        for window in chosen.windows(3) {
+            assert_ne!(window[0], window[1]);
+            assert_ne!(window[0], window[2]);
+            assert_ne!(window[1], window[2]);
+        }
+
+        //A window bigger than the library still chooses something
+        let mut selector = Selector::new(SelectionStrategy::NoRepeat, 10);
+        assert!((0..5).all(|_| selector.select(&entries, &mut rng).is_some()));
+    }
+
+    #[test]
+    fn test_select_shuffle_bag() {
+        let entries = entries(&[1.0, 2.0, 0.0, 0.5]);
+        let entries: Vec<&LibraryEntry> = entries.iter().collect();
+        let mut selector = Selector::new(SelectionStrategy::ShuffleBag, 1);
+        let mut rng = StdRng::seed_from_u64(42);
+
+        //The weights are compared to the smallest one, so fractional weights are put in as well
+        let mut chosen: Vec<PathBuf> = (0..7).map(|_| selector.select(&entries, &mut rng).unwrap()).collect();
+        chosen.sort();
+        assert_eq!(chosen, ["0.gif", "0.gif", "1.gif", "1.gif", "1.gif", "1.gif", "3.gif"].map(PathBuf::from));
+
+        let small = self::entries(&[0.4]);
+        let small: Vec<&LibraryEntry> = small.iter().collect();
+        assert_eq!(selector.select(&small, &mut rng), Some(PathBuf::from("0.gif")));
+    }
+
+    #[test]
+    fn test_select_shuffle_bag_without_repeat() {
+        //The same path twice, as well as a path with a higher weight, are in the bag several times
+        let mut entries = entries(&[1.0, 1.0, 1.0]);
+        entries[1].path = PathBuf::from("0.gif");
+        let entries: Vec<&LibraryEntry> = entries.iter().collect();
+        let mut selector = Selector::new(SelectionStrategy::ShuffleBag, 1);
+        let mut rng = StdRng::seed_from_u64(7);
+
+        //The last animation of a bag is never the first one of the next bag
+        let chosen: Vec<PathBuf> = (0..300).map(|_| selector.select(&entries, &mut rng).unwrap()).collect();
+        for bag in chosen.chunks(3).collect::<Vec<_>>().windows(2) {
+            assert_ne!(bag[0][2], bag[1][0]);
+        }
+    }
+
+    #[test]
+    fn test_select_weighted() {
+        let entries = entries(&[0.0, 1.0]);
+        let entries: Vec<&LibraryEntry> = entries.iter().collect();
+        let mut rng = StdRng::seed_from_u64(42);
+
+        for strategy in [SelectionStrategy::Uniform, SelectionStrategy::Weighted, SelectionStrategy::LeastRecent, SelectionStrategy::NoRepeat] {
+            let mut selector = Selector::new(strategy, 1);
+            assert!((0..10).all(|_| selector.select(&entries, &mut rng) == Some(PathBuf::from("1.gif"))));
+        }
+
+        let mut selector = Selector::new(SelectionStrategy::Uniform, 1);
+        assert!(selector.select(&[], &mut rng).is_none());
+    }
+}
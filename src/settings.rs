@@ -0,0 +1,194 @@
+use std::path::PathBuf;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use log::info;
+use once_cell::sync::Lazy;
+
+use crate::color::{Color, ColorPalette};
+
+///The slowest playback speed, that can be set
+pub const MIN_PLAYBACK_SPEED: f32 = 0.1;
+
+///The fastest playback speed, that can be set
+pub const MAX_PLAYBACK_SPEED: f32 = 10.0;
+
+/// Settings, that start with the value of the arguments, but can be changed while running, e.g. over the network
+struct RuntimeSettings {
+    ///Factor for all frame and blink delays
+    playback_speed: f32,
+
+    ///Color for base, blinks and grayscale animations, if set
+    default_color: Option<Color>,
+
+    ///The palette random colors are chosen from, once initialized
+    palette: Option<ColorPalette>,
+
+    ///The file the palette was read from, so it can be reloaded
+    palette_path: Option<PathBuf>,
+
+    ///Counts the changes of the default color and palette, so chosen colors know when they're outdated
+    color_generation: u64,
+}
+
+impl Default for RuntimeSettings {
+    fn default() -> Self {
+        RuntimeSettings {
+            playback_speed: 1.0,
+            default_color: None,
+            palette: None,
+            palette_path: None,
+            color_generation: 0,
+        }
+    }
+}
+
+impl RuntimeSettings {
+    /// Change the default color, see `set_default_color()`
+    fn set_default_color(&mut self, color: Option<Color>) {
+        self.default_color = color;
+        self.color_generation += 1;
+    }
+
+    /// Replace the palette, see `set_palette()`
+    fn set_palette(&mut self, palette: ColorPalette, path: Option<PathBuf>) {
+        self.palette = Some(palette);
+        self.palette_path = path.or(self.palette_path.take());
+        self.color_generation += 1;
+    }
+}
+
+///The settings of the running program
+static SETTINGS: Lazy<RwLock<RuntimeSettings>> = Lazy::new(|| RwLock::new(RuntimeSettings::default()));
+
+/// Get the settings to read them
+fn read() -> RwLockReadGuard<'static, RuntimeSettings> {
+    //Poisoned settings are still valid settings
+    SETTINGS.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Get the settings to change them
+fn write() -> RwLockWriteGuard<'static, RuntimeSettings> {
+    SETTINGS.write().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Initialize the runtime settings with the values of the arguments. The palette is initialized on its own
+///
+/// # Input
+/// * `playback_speed`: The playback speed of the arguments
+/// * `default_color`: The default color of the arguments
+pub fn init_settings(playback_speed: f32, default_color: Option<Color>) {
+    set_playback_speed(playback_speed);
+    set_default_color(default_color);
+}
+
+/// Get the current playback speed
+pub fn playback_speed() -> f32 {
+    read().playback_speed
+}
+
+/// Change the playback speed. It's clamped to a sensible range
+///
+/// # Input
+/// `speed`: The wanted playback speed
+///
+/// # Output
+/// The playback speed, that is actually used
+pub fn set_playback_speed(speed: f32) -> f32 {
+    let speed = clamp_playback_speed(speed);
+    write().playback_speed = speed;
+    info!("Playback speed is now {}", speed);
+
+    speed
+}
+
+/// Get the current default color
+pub fn default_color() -> Option<Color> {
+    read().default_color
+}
+
+/// Change the default color
+///
+/// # Input
+/// `color`: The new default color, or `None` to keep the colors of the animations
+pub fn set_default_color(color: Option<Color>) {
+    write().set_default_color(color);
+    info!("Default color is now {}", color.map(|color| format!("#{}", color)).unwrap_or("None".to_owned()));
+}
+
+/// Use the current palette, e.g. to choose a color from it
+///
+/// # Input
+/// `f`: The function that needs the palette
+///
+/// # Output
+/// Whatever the function returns, or `None` if the palette isn't initialized yet
+pub fn with_palette<T, F: FnOnce(&mut ColorPalette) -> T>(f: F) -> Option<T> {
+    write().palette.as_mut().map(f)
+}
+
+/// Replace the palette
+///
+/// # Input
+/// * `palette`: The new `ColorPalette`
+/// * `path`: The file the palette was read from, if any. The last file is kept otherwise, so it can still be reloaded
+pub fn set_palette(palette: ColorPalette, path: Option<PathBuf>) {
+    info!("Palette is now {} color/colors chosen by mode: {}", palette.colors.len(), palette.mode);
+    write().set_palette(palette, path);
+}
+
+/// Get the file the palette was read from
+pub fn palette_path() -> Option<PathBuf> {
+    read().palette_path.clone()
+}
+
+/// Get how often the default color or palette changed. Once it changes, chosen colors are outdated
+pub fn color_generation() -> u64 {
+    read().color_generation
+}
+
+/// Clamp a playback speed between `MIN_PLAYBACK_SPEED` and `MAX_PLAYBACK_SPEED`. Invalid values fall back to 1.0
+pub fn clamp_playback_speed(speed: f32) -> f32 {
+    if speed.is_nan() || speed <= 0.0 {
+        return 1.0;
+    }
+
+    speed.clamp(MIN_PLAYBACK_SPEED, MAX_PLAYBACK_SPEED)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::{BLUE, PaletteMode, RED};
+
+    use super::*;
+
+    #[test]
+    fn test_clamp_playback_speed() {
+        assert_eq!(clamp_playback_speed(1.5), 1.5);
+        assert_eq!(clamp_playback_speed(0.01), MIN_PLAYBACK_SPEED);
+        assert_eq!(clamp_playback_speed(100.0), MAX_PLAYBACK_SPEED);
+        assert_eq!(clamp_playback_speed(0.0), 1.0);
+        assert_eq!(clamp_playback_speed(-2.0), 1.0);
+        assert_eq!(clamp_playback_speed(f32::NAN), 1.0);
+    }
+
+    #[test]
+    fn test_set_palette() {
+        let mut settings = RuntimeSettings::default();
+        settings.set_palette(ColorPalette::uniform(vec![RED], PaletteMode::Sequential), Some(PathBuf::from("palette.json")));
+        settings.set_palette(ColorPalette::uniform(vec![RED, BLUE], PaletteMode::Sequential), None);
+
+        assert_eq!(settings.palette_path, Some(PathBuf::from("palette.json")));
+        assert_eq!(settings.palette.map(|palette| palette.colors), Some(vec![RED, BLUE]));
+        assert_eq!(settings.color_generation, 2);
+    }
+
+    #[test]
+    fn test_set_default_color() {
+        let mut settings = RuntimeSettings::default();
+        settings.set_default_color(Some(RED));
+        settings.set_default_color(None);
+
+        assert_eq!(settings.default_color, None);
+        assert_eq!(settings.color_generation, 2);
+    }
+}
@@ -1,17 +1,74 @@
+use std::cell::Cell;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex, MutexGuard};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::thread;
 use std::time::Duration;
 
 use log::{error, info, warn};
-use rand::{Rng, thread_rng};
-use rand::seq::SliceRandom;
+use rand::Rng;
 
 use crate::arguments::{ARGUMENTS, fallback_arguments};
-use crate::color::{get_base_or_blink_color, get_random_color_from_palette};
-use crate::file_operations::{BASE_PATH, BLINK_PATH, files_in_directory, OTHER_PATH, Playlist, read_playlist, STARTUP_PATH};
+use crate::color::{Color, get_base_or_blink_color, get_color_from_palette};
+use crate::file_operations::{BASE_PATH, BLINK_PATH, OTHER_PATH, Playlist, read_playlist, STARTUP_PATH};
+use crate::library::{CachedLibrary, LibraryEntry};
+use crate::random::with_rng;
+use crate::selection::Selector;
+use crate::playback::{Playback, QueuedAnimation};
 use crate::renderer::{play_animation_from_path, Renderer};
+use crate::settings::{color_generation, playback_speed};
+
+/// Where the base and blink animations of a cycle are and how TASBot blinks in between
+pub struct CycleSettings {
+    /// The path to the base animation
+    pub base_path: PathBuf,
+
+    /// The directory of all blink animations
+    pub blink_path: PathBuf,
+
+    /// Patterns of files and folders in the blink directory, that are to ignore
+    pub ignore_patterns: Vec<String>,
+
+    /// The maximum count of blinks per cycle
+    pub max_blinks: u8,
+
+    /// The smallest delay between blinks in milliseconds
+    pub min_delay: u16,
+
+    /// The biggest delay between blinks in milliseconds
+    pub max_delay: u16,
+}
+
+impl CycleSettings {
+    /// Create the settings of a cycle from the arguments and the default paths
+    pub fn from_arguments() -> Self {
+        let binding = fallback_arguments();
+        let args = ARGUMENTS.get().unwrap_or(&binding);
+
+        CycleSettings {
+            base_path: PathBuf::from(BASE_PATH),
+            blink_path: PathBuf::from(BLINK_PATH),
+            ignore_patterns: args.ignore_patterns.clone(),
+            max_blinks: args.max_blinks,
+            min_delay: args.min_delay,
+            max_delay: args.max_delay,
+        }
+    }
+}
+
+/// Animations the flow chooses from, together with how they're chosen
+pub struct Animations {
+    /// The library of the animations, that is only scanned again when it changed
+    pub library: CachedLibrary,
+
+    /// The `Selector` that chooses from the library
+    pub selector: Selector,
+}
+
+impl Animations {
+    pub fn new(library: CachedLibrary, selector: Selector) -> Self {
+        Animations { library, selector }
+    }
+}
 
 /// Start running the eyes. Check on some given conditions, like playlists
 ///
@@ -22,7 +79,7 @@ use crate::renderer::{play_animation_from_path, Renderer};
 ///
 /// # Todo
 /// Add a few more arguments, to provide some more specific modes the older software has to over
-pub fn start_eyes<T: Renderer>(mut renderer: T, queue: Arc<Mutex<Vec<PathBuf>>>, running: Arc<AtomicBool>) {
+pub fn start_eyes<T: Renderer>(mut renderer: T, queue: Arc<Mutex<Vec<QueuedAnimation>>>, running: Arc<AtomicBool>) {
     let binding = fallback_arguments();
     let args = ARGUMENTS.get().unwrap_or(&binding);
 
@@ -60,9 +117,9 @@ pub fn start_eyes<T: Renderer>(mut renderer: T, queue: Arc<Mutex<Vec<PathBuf>>>,
 /// `running`: An `AtomicBool` that indicates, if the loop should be continued
 fn play_playlist<T: Renderer>(renderer: &mut T, playlist: Playlist, use_rand_color: bool) {
     for entry in playlist.entries {
-        let path = PathBuf::from(entry);
+        let path = PathBuf::from(entry.path());
 
-        play_animation_from_path(renderer, path, get_base_or_blink_color(use_rand_color));
+        play_animation_from_path(renderer, path, get_base_or_blink_color(use_rand_color), &entry.playback());
     }
 }
 
@@ -76,7 +133,7 @@ fn play_playlist<T: Renderer>(renderer: &mut T, playlist: Playlist, use_rand_col
 /// # Todo
 /// When moving the rendering in its own thread, likely get rid of the `AtomicBool`
 /// and just kill the thread when exiting.
-fn run_eyes<T: Renderer>(renderer: &mut T, queue: Arc<Mutex<Vec<PathBuf>>>, running: Arc<AtomicBool>) {
+fn run_eyes<T: Renderer>(renderer: &mut T, queue: Arc<Mutex<Vec<QueuedAnimation>>>, running: Arc<AtomicBool>) {
     let binding = fallback_arguments();
     let args = ARGUMENTS.get().unwrap_or(&binding);
 
@@ -85,18 +142,59 @@ fn run_eyes<T: Renderer>(renderer: &mut T, queue: Arc<Mutex<Vec<PathBuf>>>, runn
         startup(renderer);
     }
 
+    //The selectors remember what was played and the libraries are only scanned again when they change, so
+    //they live as long as the flow
+    let cycle = CycleSettings::from_arguments();
+    let mut blinks = Animations::new(CachedLibrary::new(&cycle.blink_path, &cycle.ignore_patterns), Selector::new(args.blink_selection, args.selection_window));
+    let mut others = Animations::new(CachedLibrary::new(Path::new(OTHER_PATH), &args.ignore_patterns), Selector::new(args.other_selection, args.selection_window));
+
     //Normal flow
     while running.load(Ordering::SeqCst) { //todo: interrupt this somehow, to exit faster
-        show_base(renderer, args.color_overwrite && args.color_overwrite_all);
-        do_blink_cycle(renderer, args.color_overwrite && args.color_overwrite_all);
+        run_cycle(renderer, &queue, &cycle, &mut blinks, &mut others);
+    }
+}
+
+/// Run a single cycle of the normal flow, which is the base animation, a blink cycle and another animation
+///
+/// # Input
+/// `renderer`: The renderer that is to use to render the animations
+/// `queue`: The current animation queue that's shared with the network thread
+/// `cycle`: Where the base is and how TASBot blinks
+/// `blinks`: The blink `Animations`
+/// `others`: The other `Animations`, that are played when nothing is queued
+fn run_cycle<T: Renderer>(renderer: &mut T, queue: &Arc<Mutex<Vec<QueuedAnimation>>>, cycle: &CycleSettings, blinks: &mut Animations, others: &mut Animations) {
+    let binding = fallback_arguments();
+    let args = ARGUMENTS.get().unwrap_or(&binding);
+
+    //A locked color is chosen once, so base and blinks keep it for the whole cycle. Unless the default color
+    //or palette changes in between, then the locked color is chosen again
+    let use_rand_color = args.color_overwrite && args.color_overwrite_all;
+    let locked_color: Cell<Option<(u64, Option<Color>)>> = Cell::new(None);
+    let color = || {
+        if !args.lock_cycle_color {
+            return get_base_or_blink_color(use_rand_color);
+        }
 
-        let que = queue.lock();
-        match que {
-            Ok(q) => {
-                show_next_animation(renderer, q, args.color_overwrite);
+        match locked_color.get() {
+            Some((generation, color)) if generation == color_generation() => color,
+            _ => {
+                let generation = color_generation();
+                let color = get_base_or_blink_color(use_rand_color);
+                locked_color.set(Some((generation, color)));
+                color
             }
-            Err(e) => error!("Can't lock queue: {}", e.to_string())
         }
+    };
+
+    show_base(renderer, cycle, &color);
+    do_blink_cycle(renderer, cycle, blinks, &color);
+
+    let que = queue.lock();
+    match que {
+        Ok(q) => {
+            show_next_animation(renderer, q, others, args.color_overwrite);
+        }
+        Err(e) => error!("Can't lock queue: {}", e.to_string())
     }
 }
 
@@ -107,7 +205,7 @@ fn run_eyes<T: Renderer>(renderer: &mut T, queue: Arc<Mutex<Vec<PathBuf>>>, runn
 fn startup<T: Renderer>(renderer: &mut T) {
     info!("Play startup animation");
     let startup_anim_path = Path::new(STARTUP_PATH);
-    play_animation_from_path(renderer, startup_anim_path.to_path_buf(), None);
+    play_animation_from_path(renderer, startup_anim_path.to_path_buf(), None, &Playback::default());
     info!("Done playing startup animation");
 }
 
@@ -115,18 +213,15 @@ fn startup<T: Renderer>(renderer: &mut T) {
 ///
 /// # Input
 /// `renderer`: The renderer that is to use to render the animations
-/// `use_rand_color`: Indicating, if a random color should be selected
-fn show_base<T: Renderer>(renderer: &mut T, use_rand_color: bool) {
+/// `cycle`: Where the base is and how TASBot blinks
+/// `color`: Gets the color the base is rendered with
+fn show_base<T: Renderer>(renderer: &mut T, cycle: &CycleSettings, color: &dyn Fn() -> Option<Color>) {
     info!("Play base animation");
-    let default_args = fallback_arguments();
-    let args = ARGUMENTS.get().unwrap_or(&default_args);
 
     //skip base, when no blinks at all
-    if args.max_blinks != 0 && args.min_delay != 0 {
-        let base_path = Path::new(BASE_PATH);
-
+    if cycle.max_blinks != 0 && cycle.min_delay != 0 {
         //Render with that color, whatever it is now
-        play_animation_from_path(renderer, base_path.to_path_buf(), get_base_or_blink_color(use_rand_color));
+        play_animation_from_path(renderer, cycle.base_path.clone(), color(), &Playback::default());
     }
     info!("Done playing base animation");
 }
@@ -135,41 +230,40 @@ fn show_base<T: Renderer>(renderer: &mut T, use_rand_color: bool) {
 ///
 /// # Input
 /// `renderer`: The renderer that is to use to render the animations
-/// `use_rand_color`: Indicating, if a random color should be selected
-fn do_blink_cycle<T: Renderer>(renderer: &mut T, use_rand_color: bool) {
+/// `cycle`: How TASBot blinks
+/// `blinks`: The blink `Animations`
+/// `color`: Gets the color of every blink
+fn do_blink_cycle<T: Renderer>(renderer: &mut T, cycle: &CycleSettings, blinks: &mut Animations, color: &dyn Fn() -> Option<Color>) {
     info!("Enter blink cycle");
-    let default_args = fallback_arguments();
-    let args = ARGUMENTS.get().unwrap_or(&default_args);
 
     //Get count of this cycles blinks
-    let blink_amount = get_blink_amount(args.max_blinks);
+    let blink_amount = with_rng(|rng| get_blink_amount(cycle.max_blinks, rng));
     info!("Blinking {} time/times", blink_amount);
 
     //Get a list of blink animations
-    let blink_anims_path = Path::new(BLINK_PATH);
-    let files = files_in_directory(&blink_anims_path);
+    let library = blinks.library.get();
 
     //Init sleep
-    blink_sleep(get_blink_delay(args.min_delay, args.max_delay, args.playback_speed) as u64);
+    blink_sleep(with_rng(|rng| get_blink_delay(cycle.min_delay, cycle.max_delay, playback_speed(), rng)), renderer);
 
     //Then blink the chosen amount of times
     for _ in 0..blink_amount {
-        match &files {
-            Ok(files) => {
-                let mut rng = thread_rng();
-                let random_blink = files.choose(&mut rng);
+        match &library {
+            Ok(library) => {
+                let entries: Vec<&LibraryEntry> = library.entries.iter().collect();
+                let random_blink = with_rng(|rng| blinks.selector.select(&entries, rng));
                 match random_blink {
                     None => { warn!("Can't choose a random animation"); }
                     Some(path) => {
-                        play_animation_from_path(renderer, path.to_path_buf(), get_base_or_blink_color(use_rand_color));
+                        play_animation_from_path(renderer, path, color(), &Playback::default());
                     }
                 }
             }
             Err(err) => {
-                warn!("Can't read files in directory ({}): {}", blink_anims_path.to_str().unwrap_or("Invalid path"), err.to_string());
+                warn!("Can't read files in directory ({}): {}", cycle.blink_path.to_str().unwrap_or("Invalid path"), err.to_string());
             }
         }
-        blink_sleep(get_blink_delay(args.min_delay, args.max_delay, args.playback_speed) as u64);
+        blink_sleep(with_rng(|rng| get_blink_delay(cycle.min_delay, cycle.max_delay, playback_speed(), rng)), renderer);
     }
     info!("Exit blink cycle");
 }
@@ -179,60 +273,81 @@ fn do_blink_cycle<T: Renderer>(renderer: &mut T, use_rand_color: bool) {
 /// # Input
 /// `renderer`: The renderer that is to use to render the animations
 /// `queue`: The current animation queue that's shared with the network thread
+/// `others`: The `Animations`, that the next one is chosen from, when nothing is queued
 /// `use_rand_color`: Indicating, if a random color should be selected
-fn show_next_animation<T: Renderer>(renderer: &mut T, mut queue: MutexGuard<Vec<PathBuf>>, use_rand_color: bool) {
+fn show_next_animation<T: Renderer>(renderer: &mut T, mut queue: MutexGuard<Vec<QueuedAnimation>>, others: &mut Animations, use_rand_color: bool) {
     info!("Play other animation");
+    let default_args = fallback_arguments();
+    let args = ARGUMENTS.get().unwrap_or(&default_args);
 
-    let path = queue.pop();
-    match path {
+    let next = queue.pop();
+    match next {
         None => {
-            //Queue is empty, create a new one
-            let other_path = Path::new(OTHER_PATH);
-            let files = files_in_directory(other_path);
-            match files {
-                Ok(mut files) => {
-                    //Shuffle all files
-                    let mut rng = thread_rng();
-                    files.shuffle(&mut rng);
-
-                    //Make space for queue
-                    queue.clear();
-                    queue.extend(files);
-
-                    //If new queue is not empty, start over again
-                    if queue.len() > 0 {
-                        info!("Created new queue");
-
-                        //Recursive call itself, to actually show a animation
-                        show_next_animation(renderer, queue, use_rand_color);
-                    } else {
-                        let message = "Directory seems empty, please check!";
-                        error!("{}", message);
-                        panic!("{}", message)
+            //Queue is empty, choose one from the library
+            match others.library.get() {
+                Ok(library) => {
+                    //Only use the wanted animations
+                    let entries: Vec<&LibraryEntry> = match &args.tag {
+                        None => library.entries.iter().collect(),
+                        Some(tag) => library.with_tag(tag),
+                    };
+                    if let (true, Some(tag)) = (entries.is_empty(), &args.tag) {
+                        warn!("No animation is tagged with \"{}\". Known tags are: {:?}", tag, library.tags());
+                    }
+
+                    match with_rng(|rng| others.selector.select(&entries, rng)) {
+                        Some(path) => {
+                            //Push it to the queue and call itself, to actually show the animation
+                            queue.push(QueuedAnimation::from(path));
+                            show_next_animation(renderer, queue, others, use_rand_color);
+                        }
+                        None => {
+                            let message = "Directory seems empty or no animation has the wanted tag, please check!";
+                            error!("{}", message);
+                            panic!("{}", message)
+                        }
                     }
                 }
                 Err(err) => {
-                    warn!("Can't read directory ({}): {}", other_path.to_str().unwrap_or("Invalid path"), err.to_string());
+                    warn!("Can't read directory ({}): {}", OTHER_PATH, err);
                 }
             }
         }
-        Some(path) => {
+        Some(next) => {
             //Queue is not empty, play animation
-            let color = if use_rand_color { Some(get_random_color_from_palette()) } else { None };
-            play_animation_from_path(renderer, path, color);
+            let color = if use_rand_color { Some(get_color_from_palette()) } else { None };
+            play_animation_from_path(renderer, next.path, color, &next.playback);
         }
     }
 
     info!("Done playing other animation");
 }
 
-/// Sleep for a given amount of time between blinks
+/// Sleep for a given amount of time between blinks. If the colors of the renderer move over time, it's
+/// refreshed in between, so they keep moving while the base is shown
 ///
 /// # Inputs
 /// `delay`: The delay that is to sleep
-fn blink_sleep(delay: u64) {
+/// `renderer`: The renderer, whose `Clock` is to sleep with
+fn blink_sleep<T: Renderer>(delay: u64, renderer: &mut T) {
     info!("Sleeping for {} ms for blink", delay);
-    thread::sleep(Duration::from_millis(delay));
+    let delay = Duration::from_millis(delay);
+
+    match renderer.refresh_interval() {
+        None => renderer.clock().sleep(delay),
+        Some(interval) => {
+            let end = renderer.clock().now() + delay;
+            loop {
+                let now = renderer.clock().now();
+                if now >= end {
+                    break;
+                }
+
+                renderer.clock().sleep(interval.min(end - now));
+                renderer.refresh();
+            }
+        }
+    }
 }
 
 /// Get how long a sleep delay for a blink should be
@@ -241,15 +356,15 @@ fn blink_sleep(delay: u64) {
 /// * `min_delay`: The smallest allowed duration between blinks
 /// * `max_delay`: The biggest allowed duration between blinks
 /// * `playback_speed`: A factor, that will be multiplied with the result
+/// * `rng`: The random number generator to use
 ///
 /// # Output
 /// The delay `u64` that gonna be slept
-fn get_blink_delay(min_delay: u16, max_delay: u16, playback_speed: f32) -> u64 {
+fn get_blink_delay<R: Rng>(min_delay: u16, max_delay: u16, playback_speed: f32, rng: &mut R) -> u64 {
     if min_delay == max_delay {
         return ((min_delay as f32) * (1.0 / playback_speed)) as u64;
     }
 
-    let mut rng = rand::thread_rng();
     let delay: u16 = rng.gen_range(min_delay..=max_delay);
     ((delay as f32) * (1.0 / playback_speed)) as u64 //return
 }
@@ -257,32 +372,86 @@ fn get_blink_delay(min_delay: u16, max_delay: u16, playback_speed: f32) -> u64 {
 /// The count how many times TASBot should blink in a blinking cycle
 ///
 /// # Input
-/// `max_blinks`: The maximum allowed count of blinks
+/// * `max_blinks`: The maximum allowed count of blinks
+/// * `rng`: The random number generator to use
 ///
 /// # Output
 /// How many times TASBot is gonna blink
-fn get_blink_amount(max_blinks: u8) -> u8 {
+fn get_blink_amount<R: Rng>(max_blinks: u8, rng: &mut R) -> u8 {
     if max_blinks <= 0 {
         return 0;
     }
 
-    let mut rng = thread_rng();
     rng.gen_range(1..=max_blinks) //return
 }
 
 #[cfg(test)]
 mod tests {
+    use std::fs;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use tempdir::TempDir;
+
+    use crate::clock::Clock;
+    use crate::gif::fixtures::{RecordingRenderer, write_text_animation};
+    use crate::selection::SelectionStrategy;
+
     use super::*;
 
+    #[test]
+    fn test_blink_sleep_refreshes() {
+        let mut renderer = RecordingRenderer { refresh_interval: Some(Duration::from_millis(50)), ..RecordingRenderer::new() };
+        blink_sleep(1020, &mut renderer);
+        assert_eq!(renderer.refreshes, 21);
+        assert_eq!(renderer.clock.now(), Duration::from_millis(1020));
+
+        //Renderers without moving colors just sleep
+        let mut renderer = RecordingRenderer::new();
+        blink_sleep(1000, &mut renderer);
+        assert_eq!(renderer.refreshes, 0);
+        assert_eq!(renderer.clock.now(), Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_run_cycle() {
+        let temp_dir = TempDir::new("test_run_cycle").unwrap();
+        let base_path = temp_dir.path().join("base.txt");
+        let blink_path = temp_dir.path().join("blinks");
+        let path = temp_dir.path().join("anim.txt");
+        fs::create_dir(&blink_path).unwrap();
+        write_text_animation(&base_path, &[300]);
+        write_text_animation(&blink_path.join("blink.txt"), &[200]);
+        write_text_animation(&path, &[100, 50]);
+
+        let mut renderer = RecordingRenderer::new();
+        let queue = Arc::new(Mutex::new(vec![QueuedAnimation::from(path)]));
+        let cycle = CycleSettings { base_path, blink_path, ignore_patterns: Vec::new(), max_blinks: 1, min_delay: 1000, max_delay: 1000 };
+        let mut blinks = Animations::new(CachedLibrary::new(&cycle.blink_path, &[]), Selector::new(SelectionStrategy::NoRepeat, 1));
+        let mut others = Animations::new(CachedLibrary::new(&temp_dir.path().join("others"), &[]), Selector::new(SelectionStrategy::ShuffleBag, 1));
+
+        //Base, a blink delay, the only blink, another blink delay and the queued animation
+        run_cycle(&mut renderer, &queue, &cycle, &mut blinks, &mut others);
+
+        assert_eq!(renderer.shown, vec![
+            (Duration::ZERO, 30),
+            (Duration::from_millis(1300), 20),
+            (Duration::from_millis(2500), 10),
+            (Duration::from_millis(2600), 5),
+        ]);
+        assert_eq!(renderer.clock.now(), Duration::from_millis(2650));
+        assert!(queue.lock().unwrap().is_empty());
+    }
+
     //min_delay == max_delay
     #[test]
     fn test_get_blink_delay_min_delay_equals_max_delay() {
         let mut rng = rand::thread_rng();
 
-        let delay = get_blink_delay(2000, 2000, 1.0);
+        let delay = get_blink_delay(2000, 2000, 1.0, &mut rng);
         assert_eq!(delay, 2000);
 
-        let delay = get_blink_delay(2000, 2000, 2.0);
+        let delay = get_blink_delay(2000, 2000, 2.0, &mut rng);
         assert_eq!(delay, 1000);
     }
 
@@ -291,10 +460,33 @@ mod tests {
     fn test_get_blink_delay_min_delay_less_than_max_delay() {
         let mut rng = rand::thread_rng();
 
-        let delay = get_blink_delay(1000, 2000, 1.0);
+        let delay = get_blink_delay(1000, 2000, 1.0, &mut rng);
         assert!(delay >= 1000 && delay <= 2000);
 
-        let delay = get_blink_delay(1000, 2000, 2.0);
+        let delay = get_blink_delay(1000, 2000, 2.0, &mut rng);
         assert!(delay >= 500 && delay <= 1000);
     }
+
+    #[test]
+    fn test_get_blink_delay_seeded() {
+        let mut rng_a = StdRng::seed_from_u64(42);
+        let mut rng_b = StdRng::seed_from_u64(42);
+
+        let delays_a: Vec<u64> = (0..10).map(|_| get_blink_delay(1000, 2000, 1.0, &mut rng_a)).collect();
+        let delays_b: Vec<u64> = (0..10).map(|_| get_blink_delay(1000, 2000, 1.0, &mut rng_b)).collect();
+        assert_eq!(delays_a, delays_b);
+    }
+
+    #[test]
+    fn test_get_blink_amount() {
+        let mut rng = StdRng::seed_from_u64(42);
+        assert_eq!(get_blink_amount(0, &mut rng), 0);
+        assert!((0..20).all(|_| (1..=4).contains(&get_blink_amount(4, &mut rng))));
+
+        let mut rng_a = StdRng::seed_from_u64(7);
+        let mut rng_b = StdRng::seed_from_u64(7);
+        let amounts_a: Vec<u8> = (0..10).map(|_| get_blink_amount(8, &mut rng_a)).collect();
+        let amounts_b: Vec<u8> = (0..10).map(|_| get_blink_amount(8, &mut rng_b)).collect();
+        assert_eq!(amounts_a, amounts_b);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,125 @@
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use crate::file_operations::read_metadata;
+use crate::gif::read_animation;
+use crate::library::Library;
+use crate::renderer::TINY_DELAY;
+
+#[derive(PartialEq, Debug)]
+/// A problem, that was found in an animation
+pub struct Issue {
+    /// The path to the animation
+    pub path: PathBuf,
+
+    /// What's wrong with the animation
+    pub message: String,
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+/// Check animations for problems. Directories are searched recursively like the animation library
+///
+/// # Input
+/// * `paths`: Paths to animations or directories with animations
+/// * `ignore_patterns`: Patterns of files and folders, that are to ignore in directories
+///
+/// # Output
+/// All `Issue`s that were found
+pub fn check_paths(paths: &[PathBuf], ignore_patterns: &[String]) -> Vec<Issue> {
+    let mut issues: Vec<Issue> = Vec::new();
+
+    for path in paths {
+        if path.is_dir() {
+            match Library::scan(path, ignore_patterns) {
+                Ok(library) => {
+                    for entry in library.entries {
+                        issues.extend(check_animation(&entry.path));
+                    }
+                }
+                Err(e) => issues.push(Issue { path: path.clone(), message: format!("Can't read directory: {}", e) }),
+            }
+        } else {
+            issues.extend(check_animation(path));
+        }
+    }
+
+    issues
+}
+
+/// Check a single animation for problems:
+/// * It can't be read
+/// * It has no frames
+/// * Frames have a delay of 0 or 10 ms, which most viewers don't play as they are, unless its metadata
+///   sets a minimum frame delay
+///
+/// # Input
+/// The path to the animation
+///
+/// # Output
+/// All `Issue`s of the animation
+pub fn check_animation(path: &Path) -> Vec<Issue> {
+    let issue = |message: String| Issue { path: path.to_path_buf(), message };
+
+    let anim = match read_animation(&path.to_path_buf()) {
+        Ok(anim) => anim,
+        Err(e) => return vec![issue(format!("Can't read animation: {}", e))],
+    };
+
+    if anim.frames.is_empty() {
+        return vec![issue("Animation has no frames".to_owned())];
+    }
+
+    let tiny_frames: Vec<String> = anim.frames.iter()
+        .enumerate()
+        .filter(|(_, frame)| frame.delay <= TINY_DELAY)
+        .map(|(index, _)| index.to_string())
+        .collect();
+
+    if !tiny_frames.is_empty() && read_metadata(path).min_frame_delay.is_none() {
+        return vec![issue(format!("Frames {} have a delay of {} ms or less, which depends on the frame delay policy",
+                                  tiny_frames.join(", "), TINY_DELAY * 10))];
+    }
+
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempdir::TempDir;
+
+    use crate::gif::fixtures::write_text_animation;
+
+    use super::*;
+
+    #[test]
+    fn test_check_paths() {
+        let temp_dir = TempDir::new("test_check_paths").unwrap();
+        let dir = temp_dir.path();
+        fs::create_dir(dir.join("sub")).unwrap();
+
+        let fine = dir.join("fine.txt");
+        let tiny = dir.join("sub/tiny.txt");
+        let overwritten = dir.join("overwritten.txt");
+        let broken = dir.join("broken.gif");
+        write_text_animation(&fine, &[100]);
+        write_text_animation(&tiny, &[100, 0, 10]);
+        write_text_animation(&overwritten, &[0]);
+        fs::write(dir.join("overwritten.json"), r#"{"min_frame_delay": 50}"#).unwrap();
+        fs::write(&broken, "GIF89a").unwrap();
+
+        let issues = check_paths(&[dir.to_path_buf()], &[]);
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].path, broken);
+        assert_eq!(issues[1], Issue { path: tiny.clone(), message: "Frames 1, 2 have a delay of 10 ms or less, which depends on the frame delay policy".to_owned() });
+
+        assert!(check_paths(&[fine], &[]).is_empty());
+        assert_eq!(check_paths(&[dir.join("missing.gif")], &[]).len(), 1);
+    }
+}